@@ -1,3 +1,6 @@
 #[cfg(target_os = "windows")]
 #[path = "win32.rs"]
-pub mod dialog;
\ No newline at end of file
+pub mod dialog;
+#[cfg(target_os = "windows")]
+pub mod utils;
+pub mod options;
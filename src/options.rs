@@ -0,0 +1,344 @@
+use std::{
+    fmt::{ Debug, Display, Formatter },
+    ops::{ BitAnd, BitAndAssign, BitOr, BitOrAssign, Not, Sub }
+};
+
+/// Behaviour flags for a file dialog. The values match the shell's `FOS_*` options, so they can be
+/// converted to and from the raw value returned by `IFileDialog::GetOptions`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DialogFlags(u32);
+
+impl DialogFlags {
+    pub const OVERWRITE_PROMPT: Self = Self(0x2);
+    pub const STRICT_FILE_TYPES: Self = Self(0x4);
+    pub const NO_CHANGE_DIR: Self = Self(0x8);
+    pub const PICK_FOLDERS: Self = Self(0x20);
+    pub const FORCE_FILESYSTEM: Self = Self(0x40);
+    pub const ALL_NON_STORAGE_ITEMS: Self = Self(0x80);
+    pub const NO_VALIDATE: Self = Self(0x100);
+    pub const ALLOW_MULTISELECT: Self = Self(0x200);
+    pub const PATH_MUST_EXIST: Self = Self(0x800);
+    pub const FILE_MUST_EXIST: Self = Self(0x1000);
+    pub const CREATE_PROMPT: Self = Self(0x2000);
+    pub const SHARE_AWARE: Self = Self(0x4000);
+    pub const NO_READONLY_RETURN: Self = Self(0x8000);
+    pub const NO_TEST_FILE_CREATE: Self = Self(0x10000);
+    pub const HIDE_MRU_PLACES: Self = Self(0x20000);
+    pub const HIDE_PINNED_PLACES: Self = Self(0x40000);
+    pub const NO_DEREFERENCE_LINKS: Self = Self(0x100000);
+    pub const OK_BUTTON_NEEDS_INTERACTION: Self = Self(0x200000);
+    pub const DONT_ADD_TO_RECENT: Self = Self(0x2000000);
+    pub const FORCE_SHOW_HIDDEN: Self = Self(0x10000000);
+    pub const DEFAULT_NO_MINI_MODE: Self = Self(0x20000000);
+    pub const FORCE_PREVIEW_PANE_ON: Self = Self(0x40000000);
+    pub const SUPPORT_STREAMABLE_ITEMS: Self = Self(0x80000000);
+
+    const NAMES: [(&'static str, Self); 23] = [
+        ("OVERWRITE_PROMPT", Self::OVERWRITE_PROMPT),
+        ("STRICT_FILE_TYPES", Self::STRICT_FILE_TYPES),
+        ("NO_CHANGE_DIR", Self::NO_CHANGE_DIR),
+        ("PICK_FOLDERS", Self::PICK_FOLDERS),
+        ("FORCE_FILESYSTEM", Self::FORCE_FILESYSTEM),
+        ("ALL_NON_STORAGE_ITEMS", Self::ALL_NON_STORAGE_ITEMS),
+        ("NO_VALIDATE", Self::NO_VALIDATE),
+        ("ALLOW_MULTISELECT", Self::ALLOW_MULTISELECT),
+        ("PATH_MUST_EXIST", Self::PATH_MUST_EXIST),
+        ("FILE_MUST_EXIST", Self::FILE_MUST_EXIST),
+        ("CREATE_PROMPT", Self::CREATE_PROMPT),
+        ("SHARE_AWARE", Self::SHARE_AWARE),
+        ("NO_READONLY_RETURN", Self::NO_READONLY_RETURN),
+        ("NO_TEST_FILE_CREATE", Self::NO_TEST_FILE_CREATE),
+        ("HIDE_MRU_PLACES", Self::HIDE_MRU_PLACES),
+        ("HIDE_PINNED_PLACES", Self::HIDE_PINNED_PLACES),
+        ("NO_DEREFERENCE_LINKS", Self::NO_DEREFERENCE_LINKS),
+        ("OK_BUTTON_NEEDS_INTERACTION", Self::OK_BUTTON_NEEDS_INTERACTION),
+        ("DONT_ADD_TO_RECENT", Self::DONT_ADD_TO_RECENT),
+        ("FORCE_SHOW_HIDDEN", Self::FORCE_SHOW_HIDDEN),
+        ("DEFAULT_NO_MINI_MODE", Self::DEFAULT_NO_MINI_MODE),
+        ("FORCE_PREVIEW_PANE_ON", Self::FORCE_PREVIEW_PANE_ON),
+        ("SUPPORT_STREAMABLE_ITEMS", Self::SUPPORT_STREAMABLE_ITEMS)
+    ];
+
+    pub const fn empty() -> Self { Self(0) }
+    /// Keeps bits that don't have a named flag, so reading back and reapplying options is lossless
+    pub const fn from_bits(bits: u32) -> Self { Self(bits) }
+    pub const fn bits(&self) -> u32 { self.0 }
+    pub const fn is_empty(&self) -> bool { self.0 == 0 }
+    pub const fn contains(&self, other: Self) -> bool { self.0 & other.0 == other.0 }
+    pub const fn intersects(&self, other: Self) -> bool { self.0 & other.0 != 0 }
+    pub fn insert(&mut self, other: Self) { self.0 |= other.0 }
+    pub fn remove(&mut self, other: Self) { self.0 &= !other.0 }
+    pub fn set(&mut self, other: Self, value: bool) {
+        match value {
+            true => self.insert(other),
+            false => self.remove(other)
+        }
+    }
+
+    /// Names of the flags that are set
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.iter().map(|(n, _)| n)
+    }
+
+    /// The named flags that are set, one at a time
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, Self)> + '_ {
+        Self::NAMES.iter().filter(|(_, f)| self.contains(*f)).copied()
+    }
+
+    // bits that are set but don't have a name
+    fn unknown_bits(&self) -> u32 {
+        Self::NAMES.iter().fold(self.0, |bits, (_, f)| bits & !f.0)
+    }
+}
+
+impl BitOr for DialogFlags {
+    type Output = Self;
+    fn bitor(self, rhs: Self) -> Self { Self(self.0 | rhs.0) }
+}
+
+impl BitOrAssign for DialogFlags {
+    fn bitor_assign(&mut self, rhs: Self) { self.0 |= rhs.0 }
+}
+
+impl BitAnd for DialogFlags {
+    type Output = Self;
+    fn bitand(self, rhs: Self) -> Self { Self(self.0 & rhs.0) }
+}
+
+impl BitAndAssign for DialogFlags {
+    fn bitand_assign(&mut self, rhs: Self) { self.0 &= rhs.0 }
+}
+
+impl Sub for DialogFlags {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self { Self(self.0 & !rhs.0) }
+}
+
+impl Not for DialogFlags {
+    type Output = Self;
+    fn not(self) -> Self { Self(!self.0) }
+}
+
+impl Display for DialogFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts: Vec<String> = self.names().map(str::to_owned).collect();
+        let unknown = self.unknown_bits();
+        if unknown != 0 {
+            parts.push(format!("0x{:x}", unknown));
+        }
+        match parts.is_empty() {
+            true => write!(f, "(none)"),
+            false => write!(f, "{}", parts.join(" | "))
+        }
+    }
+}
+
+impl Debug for DialogFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DialogFlags({})", self)
+    }
+}
+
+/// Describe how a dialog's flags changed, for logging. Added flags are prefixed with `+` and
+/// removed flags with `-`, such as `+FILE_MUST_EXIST -NO_CHANGE_DIR`.
+pub fn options_diff(before: DialogFlags, after: DialogFlags) -> String {
+    let added = after - before;
+    let removed = before - after;
+    let mut parts: Vec<String> = vec![];
+    parts.extend(added.names().map(|n| format!("+{}", n)));
+    if added.unknown_bits() != 0 {
+        parts.push(format!("+0x{:x}", added.unknown_bits()));
+    }
+    parts.extend(removed.names().map(|n| format!("-{}", n)));
+    if removed.unknown_bits() != 0 {
+        parts.push(format!("-0x{:x}", removed.unknown_bits()));
+    }
+    match parts.is_empty() {
+        true => "no change".to_owned(),
+        false => parts.join(" ")
+    }
+}
+
+/// A GUID identifying a dialog to the shell, which keys remembered state such as the last folder
+/// on it. On Windows this is passed to `IFileDialog::SetClientGuid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Guid(pub u128);
+
+/// Changes to make to a dialog's default flags. Each flag can be set, cleared or left as the
+/// dialog's default, so turning a flag off is as explicit as turning it on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DialogOptions {
+    set: DialogFlags,
+    clear: DialogFlags,
+    client_guid: Option<Guid>
+}
+
+impl DialogOptions {
+    pub const fn new() -> Self { Self { set: DialogFlags::empty(), clear: DialogFlags::empty(), client_guid: None } }
+
+    /// Set (`true`) or clear (`false`) a flag, replacing any earlier choice for it
+    pub fn flag(mut self, flag: DialogFlags, value: bool) -> Self {
+        match value {
+            true => {
+                self.set.insert(flag);
+                self.clear.remove(flag);
+            },
+            false => {
+                self.clear.insert(flag);
+                self.set.remove(flag);
+            }
+        }
+        self
+    }
+
+    /// Go back to the dialog's default for a flag
+    pub fn unset(mut self, flag: DialogFlags) -> Self {
+        self.set.remove(flag);
+        self.clear.remove(flag);
+        self
+    }
+
+    /// Don't let the dialog create and delete a test file to check that the selection can be
+    /// written. This is needed for write-once media and some network locations and devices, but
+    /// it means a returned save path may turn out not to be creatable.
+    pub fn skip_test_create(self, value: bool) -> Self {
+        self.flag(DialogFlags::NO_TEST_FILE_CREATE, value)
+    }
+
+    /// Refuse files with the read-only attribute in the dialog itself
+    pub fn no_readonly_return(self, value: bool) -> Self {
+        self.flag(DialogFlags::NO_READONLY_RETURN, value)
+    }
+
+    /// Stop the dialog from changing the process's working directory. Dialogs set this by default.
+    pub fn no_change_dir(self, value: bool) -> Self {
+        self.flag(DialogFlags::NO_CHANGE_DIR, value)
+    }
+
+    /// Only accept files that exist. Clear this for "open or create" flows.
+    pub fn file_must_exist(self, value: bool) -> Self {
+        self.flag(DialogFlags::FILE_MUST_EXIST, value)
+    }
+
+    /// Show hidden and system files regardless of the user's Explorer settings
+    pub fn show_hidden(self, value: bool) -> Self {
+        self.flag(DialogFlags::FORCE_SHOW_HIDDEN, value)
+    }
+
+    /// Add the selection to the shell's recent items. Passing `false` sets `DONT_ADD_TO_RECENT`.
+    pub fn add_to_recent(self, value: bool) -> Self {
+        self.flag(DialogFlags::DONT_ADD_TO_RECENT, !value)
+    }
+
+    /// Use a separate set of remembered shell state, such as the last folder, for this dialog
+    pub fn client_guid(mut self, value: Guid) -> Self {
+        self.client_guid = Some(value);
+        self
+    }
+
+    /// Hide the recent and pinned places from the navigation pane
+    pub fn minimal_chrome(self) -> Self {
+        self.flag(DialogFlags::HIDE_MRU_PLACES | DialogFlags::HIDE_PINNED_PLACES, true)
+    }
+
+    pub fn get_set(&self) -> DialogFlags { self.set }
+    pub fn get_cleared(&self) -> DialogFlags { self.clear }
+    pub fn get_client_guid(&self) -> Option<Guid> { self.client_guid }
+    pub fn is_empty(&self) -> bool { self.set.is_empty() && self.clear.is_empty() && self.client_guid.is_none() }
+
+    /// The flags a dialog ends up with when these changes are made to `base`
+    pub fn apply(&self, base: DialogFlags) -> DialogFlags {
+        (base | self.set) - self.clear
+    }
+
+    /// Combine with changes that take precedence over these ones
+    pub fn merge(self, later: Self) -> Self {
+        Self {
+            set: (self.set - later.clear) | later.set,
+            clear: (self.clear - later.set) | later.clear,
+            client_guid: later.client_guid.or(self.client_guid)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flag_then_unset() {
+        let options = DialogOptions::new().flag(DialogFlags::FILE_MUST_EXIST, true);
+        assert_eq!(options.get_set(), DialogFlags::FILE_MUST_EXIST);
+        let options = options.unset(DialogFlags::FILE_MUST_EXIST);
+        assert!(options.is_empty());
+        assert_eq!(options.apply(DialogFlags::FILE_MUST_EXIST), DialogFlags::FILE_MUST_EXIST);
+
+        let options = DialogOptions::new().flag(DialogFlags::NO_CHANGE_DIR, false).unset(DialogFlags::NO_CHANGE_DIR);
+        assert!(options.is_empty());
+        assert_eq!(options.apply(DialogFlags::NO_CHANGE_DIR), DialogFlags::NO_CHANGE_DIR);
+    }
+
+    #[test]
+    fn later_flag_replaces_earlier() {
+        let options = DialogOptions::new().show_hidden(true).show_hidden(false);
+        assert_eq!(options.get_set(), DialogFlags::empty());
+        assert_eq!(options.get_cleared(), DialogFlags::FORCE_SHOW_HIDDEN);
+    }
+
+    #[test]
+    fn unset_flag_that_was_never_set() {
+        let options = DialogOptions::new().flag(DialogFlags::PICK_FOLDERS, true).unset(DialogFlags::FILE_MUST_EXIST);
+        assert_eq!(options.get_set(), DialogFlags::PICK_FOLDERS);
+        assert_eq!(options.get_cleared(), DialogFlags::empty());
+        assert!(DialogOptions::new().unset(DialogFlags::FILE_MUST_EXIST).is_empty());
+    }
+
+    #[test]
+    fn merge_later_layer_overrides() {
+        let earlier = DialogOptions::new()
+            .flag(DialogFlags::FILE_MUST_EXIST, true)
+            .flag(DialogFlags::NO_CHANGE_DIR, false)
+            .flag(DialogFlags::FORCE_SHOW_HIDDEN, true)
+            .client_guid(Guid(1));
+        let later = DialogOptions::new()
+            .flag(DialogFlags::FILE_MUST_EXIST, false)
+            .flag(DialogFlags::NO_CHANGE_DIR, true);
+        let merged = earlier.merge(later);
+        assert_eq!(merged.get_set(), DialogFlags::NO_CHANGE_DIR | DialogFlags::FORCE_SHOW_HIDDEN);
+        assert_eq!(merged.get_cleared(), DialogFlags::FILE_MUST_EXIST);
+        assert_eq!(merged.get_client_guid(), Some(Guid(1)));
+        assert_eq!(merged.merge(DialogOptions::new().client_guid(Guid(2))).get_client_guid(), Some(Guid(2)));
+    }
+
+    #[test]
+    fn merge_with_empty_changes_nothing() {
+        let options = DialogOptions::new().flag(DialogFlags::FILE_MUST_EXIST, true).flag(DialogFlags::NO_CHANGE_DIR, false);
+        assert_eq!(options.merge(DialogOptions::new()), options);
+        assert_eq!(DialogOptions::new().merge(options), options);
+    }
+
+    #[test]
+    fn apply_overlapping_masks() {
+        // clearing one flag of a pair that was set keeps the other
+        let options = DialogOptions::new().minimal_chrome().flag(DialogFlags::HIDE_PINNED_PLACES, false);
+        assert_eq!(options.get_set(), DialogFlags::HIDE_MRU_PLACES);
+        assert_eq!(options.get_cleared(), DialogFlags::HIDE_PINNED_PLACES);
+        let base = DialogFlags::HIDE_PINNED_PLACES | DialogFlags::NO_CHANGE_DIR;
+        assert_eq!(options.apply(base), DialogFlags::HIDE_MRU_PLACES | DialogFlags::NO_CHANGE_DIR);
+
+        // setting a pair after clearing one of them sets both
+        let options = DialogOptions::new().flag(DialogFlags::HIDE_PINNED_PLACES, false).minimal_chrome();
+        assert_eq!(options.get_cleared(), DialogFlags::empty());
+        assert_eq!(options.apply(DialogFlags::empty()), DialogFlags::HIDE_MRU_PLACES | DialogFlags::HIDE_PINNED_PLACES);
+    }
+
+    #[test]
+    fn apply_keeps_unknown_bits() {
+        let base = DialogFlags::from_bits(0x1 | DialogFlags::NO_CHANGE_DIR.bits());
+        let options = DialogOptions::new().flag(DialogFlags::NO_CHANGE_DIR, false);
+        assert_eq!(options.apply(base), DialogFlags::from_bits(0x1));
+    }
+}
@@ -0,0 +1,134 @@
+//! Conversions between Rust strings and the null-terminated UTF-16 strings used by Windows APIs.
+//!
+//! Windows strings aren't guaranteed to be valid UTF-16 (file names can contain unpaired
+//! surrogates), so the conversions from wide strings produce an [`OsString`], which can hold
+//! them without loss. Use [`OsStr::to_string_lossy`](std::ffi::OsStr::to_string_lossy) when a displayable string is needed.
+
+use std::{
+    error::Error,
+    ffi::{ OsStr, OsString },
+    fmt::{ Display, Formatter },
+    os::windows::ffi::{ OsStrExt, OsStringExt }
+};
+
+/// A string couldn't be converted because it contains a NUL character, which would cut the
+/// string short when passed to Windows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InteriorNulError {
+    /// Position of the NUL in UTF-16 code units
+    pub position: usize
+}
+
+impl Display for InteriorNulError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "String contains a NUL character at position {}", self.position)
+    }
+}
+
+impl Error for InteriorNulError {}
+
+fn terminate<I>(units: I, capacity: usize) -> Result<Vec<u16>, InteriorNulError>
+where I: Iterator<Item = u16> {
+    let mut alloc = Vec::with_capacity(capacity + 1);
+    for (position, c) in units.enumerate() {
+        if c == 0 {
+            return Err(InteriorNulError { position });
+        }
+        alloc.push(c);
+    }
+    alloc.push(0); // add null terminator
+    Ok(alloc)
+}
+
+/// Convert a string to a null-terminated UTF-16 string
+pub fn to_wide(s: &str) -> Result<Vec<u16>, InteriorNulError> {
+    terminate(s.encode_utf16(), s.len())
+}
+
+/// Convert an OS string (such as a path) to a null-terminated UTF-16 string. Unlike going through
+/// `&str`, this keeps file names that aren't valid Unicode intact.
+pub fn to_wide_os(s: &OsStr) -> Result<Vec<u16>, InteriorNulError> {
+    terminate(s.encode_wide(), s.len())
+}
+
+/// Convert a UTF-16 string to an OS string. If the slice contains a NUL, conversion stops there,
+/// so fixed size buffers filled in by Windows can be passed directly.
+pub fn from_wide(s: &[u16]) -> OsString {
+    let len = s.iter().position(|c| *c == 0).unwrap_or(s.len());
+    OsString::from_wide(&s[..len])
+}
+
+/// Convert a null-terminated UTF-16 string to an OS string. A null pointer gives an empty string.
+///
+/// # Safety
+///
+/// `s` must either be null or point to a readable, null-terminated UTF-16 string.
+pub unsafe fn from_wide_until_nul(s: *const u16) -> OsString {
+    if s.is_null() {
+        return OsString::new();
+    }
+    let mut len = 0;
+    while unsafe { *s.add(len) } != 0 {
+        len += 1;
+    }
+    OsString::from_wide(unsafe { std::slice::from_raw_parts(s, len) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a lone high surrogate, which can appear in NTFS file names but isn't valid UTF-16
+    const UNPAIRED: [u16; 3] = [b'a' as u16, 0xD800, b'b' as u16];
+
+    #[test]
+    fn empty_string() {
+        assert_eq!(to_wide("").unwrap(), vec![0]);
+        assert_eq!(to_wide_os(OsStr::new("")).unwrap(), vec![0]);
+        assert_eq!(from_wide(&[]), OsString::new());
+        assert_eq!(from_wide(&[0]), OsString::new());
+    }
+
+    #[test]
+    fn round_trip() {
+        let wide = to_wide("level3 ☆.json").unwrap();
+        assert_eq!(wide.last(), Some(&0));
+        assert_eq!(from_wide(&wide), OsString::from("level3 ☆.json"));
+    }
+
+    #[test]
+    fn interior_nul_is_rejected() {
+        assert_eq!(to_wide("ab\0c"), Err(InteriorNulError { position: 2 }));
+        assert_eq!(to_wide_os(OsStr::new("\0")), Err(InteriorNulError { position: 0 }));
+    }
+
+    #[test]
+    fn unpaired_surrogate_round_trip() {
+        let os = from_wide(&UNPAIRED);
+        assert!(os.to_str().is_none());
+        let mut expected = UNPAIRED.to_vec();
+        expected.push(0);
+        assert_eq!(to_wide_os(&os).unwrap(), expected);
+    }
+
+    #[test]
+    fn from_wide_stops_at_nul() {
+        assert_eq!(from_wide(&[b'a' as u16, 0, b'b' as u16]), OsString::from("a"));
+        // without a terminator the whole slice is used
+        assert_eq!(from_wide(&[b'a' as u16, b'b' as u16]), OsString::from("ab"));
+    }
+
+    #[test]
+    fn from_wide_until_nul_terminated() {
+        let wide = to_wide("ab").unwrap();
+        assert_eq!(unsafe { from_wide_until_nul(wide.as_ptr()) }, OsString::from("ab"));
+        // only the part before the first terminator is read
+        let wide = [b'a' as u16, 0, b'b' as u16, 0];
+        assert_eq!(unsafe { from_wide_until_nul(wide.as_ptr()) }, OsString::from("a"));
+    }
+
+    #[test]
+    fn from_wide_until_nul_null_pointer() {
+        assert_eq!(unsafe { from_wide_until_nul(std::ptr::null()) }, OsString::new());
+    }
+}
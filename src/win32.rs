@@ -1,93 +1,507 @@
 use std::{
+    collections::HashMap,
     error::Error,
+    fmt::{ Debug, Display, Formatter },
+    panic::{ self, AssertUnwindSafe },
     path::{ Path, PathBuf },
-    sync::{ Mutex, MutexGuard },
+    string::FromUtf16Error,
+    sync::{ Arc, Mutex, MutexGuard, PoisonError },
 };
 use windows::{
     core::{
+        BOOL,
         Error as WinError,
-        PCWSTR,
+        GUID,
+        HRESULT,
+        PWSTR
     },
     Win32::{
-        Foundation::HWND,
-        System::Com::{
-            CoCreateInstance,
-            CoTaskMemFree,
-            CLSCTX_ALL
+        Foundation::{ ERROR_CANCELLED, HLOCAL, HWND, LocalFree, LPARAM },
+        System::{
+            Diagnostics::Debug::{
+                FormatMessageW,
+                FORMAT_MESSAGE_ALLOCATE_BUFFER,
+                FORMAT_MESSAGE_FROM_SYSTEM,
+                FORMAT_MESSAGE_IGNORE_INSERTS
+            },
+            Com::{
+                CoCreateInstance,
+                CLSCTX_ALL
+            },
+            Threading::GetCurrentProcessId
         },
         UI::Shell::{
-            Common::COMDLG_FILTERSPEC,
-            FOS_PICKFOLDERS,
+            FILEOPENDIALOGOPTIONS,
             FileOpenDialog,
             FileSaveDialog,
+            IFileDialog,
             IFileOpenDialog,
-            IFileSaveDialog,
-            IShellItem,
-            SIGDN_FILESYSPATH,
-            SHCreateItemFromParsingName
+            IFileSaveDialog
+        },
+        UI::WindowsAndMessaging::{
+            EnumWindows,
+            GetForegroundWindow,
+            GetWindowThreadProcessId,
+            IsWindow,
+            IsWindowVisible
         }
     }
 };
 
-#[derive(Debug)]
-pub struct FileTypeFilter {
-    extension: String,
-    description: String
+use crate::{
+    options::{ DialogFlags, DialogOptions },
+    utils::wide::{ self, InteriorNulError }
+};
+
+#[cfg(feature = "archive")]
+#[path = "win32/archive.rs"]
+mod archive;
+#[path = "win32/audit.rs"]
+mod audit;
+#[path = "win32/background.rs"]
+mod background;
+#[path = "win32/batch.rs"]
+mod batch;
+#[path = "win32/collect.rs"]
+mod collect;
+#[path = "win32/config.rs"]
+mod config;
+#[path = "win32/confirm.rs"]
+mod confirm;
+#[path = "win32/detach.rs"]
+mod detach;
+#[path = "win32/drive.rs"]
+mod drive;
+#[path = "win32/events.rs"]
+mod events;
+#[path = "win32/filter.rs"]
+mod filter;
+#[path = "win32/handle.rs"]
+mod handle;
+#[path = "win32/history.rs"]
+mod history;
+#[path = "win32/macros.rs"]
+mod macros;
+#[path = "win32/manager_config.rs"]
+mod manager_config;
+#[path = "win32/metadata.rs"]
+mod metadata;
+#[path = "win32/monitor.rs"]
+mod monitor;
+#[path = "win32/notify.rs"]
+mod notify;
+#[path = "win32/pattern.rs"]
+mod pattern;
+#[path = "win32/persist.rs"]
+mod persist;
+#[path = "win32/pipeline.rs"]
+mod pipeline;
+#[cfg(feature = "regex")]
+#[path = "win32/regex.rs"]
+mod regex;
+#[path = "win32/registry.rs"]
+mod registry;
+#[path = "win32/style.rs"]
+mod style;
+
+#[cfg(feature = "archive")]
+pub use archive::{ ExtractedSelection, OpenSelection };
+pub use audit::{ AuditEntry, AuditLog, AuditOutcome, AuditStage };
+pub use background::{ DialogHandle, DialogResult, DialogStatus };
+pub use batch::BatchPaths;
+pub use confirm::{ OverwriteChoice, confirm_overwrite, unique_path };
+use config::CallSettings;
+pub use config::{ DEFAULT_DIR_VARIABLE, DialogKind, FolderAccess, InitialFolderSource, OptionLayer, PatternPolicy, ResolvedConfig, ResolvedFilter, SaveOptions, SuggestedPath };
+pub use drive::{ DriveKind, DriveSelection };
+use events::DialogEvents;
+pub use filter::{ ExtensionGrouping, FileTypeFilter, FileTypeFilterWin32, FilterError };
+#[cfg(feature = "serde")]
+pub use filter::FilterLoadError;
+pub use history::FolderHistory;
+pub use handle::{ CreateDisposition, OpenAccess, ShareMode };
+pub use manager_config::ManagerConfig;
+pub use metadata::{ DialogSelection, SelectionInfo };
+pub use notify::{ CompletionNotification, PostedMessage };
+pub use monitor::MonitorSelection;
+pub use pattern::NamePattern;
+#[cfg(feature = "regex")]
+pub use regex::{ Regex, RegexError };
+use pipeline::DialogPipeline;
+pub use registry::{ WindowId, WindowManager };
+pub use style::{ PathStyle, StyledPath, UncStyle };
+
+/// Where the manager gets the owner window for a dialog from. The owner is looked up right
+/// before each dialog is shown, so a callback can return whatever the current window is.
+pub enum OwnerSource {
+    /// A fixed window handle, captured when the manager was created
+    Window(HWND),
+    /// Fetch the window handle each time a dialog is shown. Returning `None` (or panicking) shows
+    /// the dialog without an owner.
+    Callback(Box<dyn Fn() -> Option<HWND> + Send + Sync>),
+    /// Use a visible top-level window belonging to this process, preferring the foreground
+    /// window. Shows the dialog unowned if there isn't one.
+    AutoDetect
 }
 
-impl FileTypeFilter {
-    pub const fn new(extension: String, description: String) -> Self {
-        Self { extension, description }
+impl OwnerSource {
+    pub fn resolve(&self) -> Option<HWND> {
+        match self {
+            Self::Window(hwnd) => Some(*hwnd),
+            Self::Callback(cb) => match panic::catch_unwind(AssertUnwindSafe(cb)) {
+                Ok(Some(hwnd)) => Some(hwnd),
+                Ok(None) => {
+                    log::warn!("Owner window callback returned no window, showing dialog unowned");
+                    None
+                },
+                Err(_) => {
+                    log::error!("Owner window callback panicked, showing dialog unowned");
+                    None
+                }
+            },
+            Self::AutoDetect => {
+                let window = FileDialogUtils::find_process_window();
+                match window {
+                    Some(hwnd) => log::trace!("Auto-detected owner window {:?}", hwnd),
+                    None => log::warn!("Couldn't find a window for this process, showing dialog unowned")
+                }
+                window
+            }
+        }
     }
+}
 
-    pub fn get_extension(&self) -> &str { &self.extension }
-    pub fn get_description(&self) -> &str { &self.description }
+impl Debug for OwnerSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Window(hwnd) => f.debug_tuple("Window").field(hwnd).finish(),
+            Self::Callback(_) => f.write_str("Callback"),
+            Self::AutoDetect => f.write_str("AutoDetect")
+        }
+    }
+}
+
+/// What to do when a dialog is shown while the manager has no owner window attached, or the
+/// window it has is no longer valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OwnerPolicy {
+    /// Fail with [`DialogError::NoOwnerWindow`] or [`DialogError::StaleWindowHandle`]
+    #[default]
+    Require,
+    /// Show the dialog without an owner window
+    Unowned
+}
+
+/// The owner window a dialog was actually shown with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OwnerResolution {
+    Owned(HWND),
+    /// No owner window was available
+    Unowned,
+    /// The owner window had been destroyed (or belongs to another process), so the dialog was
+    /// shown unowned instead
+    Stale(HWND)
+}
+
+impl OwnerResolution {
+    pub fn window(&self) -> Option<HWND> {
+        match self {
+            Self::Owned(hwnd) => Some(*hwnd),
+            _ => None
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct FileTypeFilterWin32 {
-    extension: Vec<u16>,
-    description: Vec<u16>
+pub enum DialogError {
+    /// The manager doesn't have an owner window attached yet and its [`OwnerPolicy`] requires one
+    NoOwnerWindow,
+    /// The owner window has been destroyed or doesn't belong to this process
+    StaleWindowHandle(HWND),
+    /// A string passed to the dialog contains a NUL character
+    InvalidString(InteriorNulError),
+    /// Two settings were used together that can't be combined
+    ConflictingOptions(&'static str),
+    /// A save path couldn't be written to, when checking with [`SaveDialog::verify_writable`]
+    NotWritable {
+        path: PathBuf,
+        source: std::io::Error
+    },
+    /// The selected file couldn't be opened
+    OpenFailed {
+        path: PathBuf,
+        source: std::io::Error
+    },
+    /// The selected file couldn't be opened because another process has it open or locked
+    FileInUse {
+        path: PathBuf,
+        source: std::io::Error
+    },
+    /// The selected file's name doesn't match the pattern from `require_name_matches`. Contains
+    /// the caller's rejection message.
+    NameMismatch {
+        path: PathBuf,
+        message: String
+    },
+    /// The selected file is larger than the dialog's size limit
+    FileTooLarge {
+        path: PathBuf,
+        size: u64,
+        limit: u64
+    },
+    /// The selected folder can't be listed or written to, when checking with
+    /// [`OpenDialog::require_folder_access`]
+    FolderNotAccessible {
+        path: PathBuf,
+        source: std::io::Error
+    },
+    /// The user accepted a wildcard name pattern instead of choosing a file, with
+    /// [`PatternPolicy::Error`]
+    PatternSelected(PathBuf),
+    /// Another dialog started with one of the `_async` functions is still open
+    AsyncPending,
+    /// A dialog shown on a worker thread panicked
+    Internal { message: String },
+    Manager(ManagerError),
+    Win32(WinError)
+}
+
+impl Display for DialogError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoOwnerWindow => write!(f, "No owner window has been attached to the file dialog manager"),
+            Self::StaleWindowHandle(hwnd) => write!(f, "Owner window {:?} is no longer valid", hwnd.0),
+            Self::InvalidString(e) => write!(f, "{}", e),
+            Self::NotWritable { path, source } => write!(f, "Can't write to {}: {}", path.display(), source),
+            Self::OpenFailed { path, source } => write!(f, "Couldn't open {}: {}", path.display(), source),
+            Self::FileInUse { path, .. } => write!(f, "{} is being used by another process", path.display()),
+            Self::NameMismatch { message, .. } => write!(f, "{}", message),
+            Self::FileTooLarge { path, size, limit } => write!(f, "{} is {} bytes, which is over the limit of {} bytes",
+                path.file_name().map_or(path.as_os_str(), |v| v).to_string_lossy(), size, limit),
+            Self::FolderNotAccessible { path, source } => write!(f, "Can't access {}: {}", path.display(), source),
+            Self::ConflictingOptions(e) => write!(f, "Conflicting dialog options: {}", e),
+            Self::PatternSelected(p) => write!(f, "No file was chosen, the selection was the pattern {}", p.display()),
+            Self::AsyncPending => write!(f, "Another file dialog is already open"),
+            Self::Internal { message } => write!(f, "File dialog failed unexpectedly: {}", message),
+            Self::Manager(e) => write!(f, "{}", e),
+            Self::Win32(e) => match FileDialogUtils::format_hresult(e.code()) {
+                Some(message) => write!(f, "{} (0x{:08X})", message, e.code().0),
+                None => write!(f, "Windows error 0x{:08X}", e.code().0)
+            }
+        }
+    }
 }
 
-impl FileTypeFilterWin32 {
-    pub fn new(extension: &str, description: &str) -> Self {
-        let extension = format!("*.{}", extension);
-        let extension = FileDialogUtils::to_win32_wide(&extension);
-        let description = FileDialogUtils::to_win32_wide(description);
-        Self { extension, description }
+impl DialogError {
+    /// The underlying HRESULT for errors that came from a Windows API call
+    pub fn hresult(&self) -> Option<HRESULT> {
+        match self {
+            Self::Win32(e) => Some(e.code()),
+            _ => None
+        }
     }
+}
 
-    pub fn get_extension(&self) -> PCWSTR { PCWSTR(self.extension.as_ptr()) }
-    pub fn get_description(&self) -> PCWSTR { PCWSTR(self.description.as_ptr()) }
+impl Error for DialogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InvalidString(e) => Some(e),
+            Self::NotWritable { source, .. }
+            | Self::OpenFailed { source, .. }
+            | Self::FileInUse { source, .. }
+            | Self::FolderNotAccessible { source, .. } => Some(source),
+            Self::Manager(e) => Some(e),
+            Self::Win32(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<InteriorNulError> for DialogError {
+    fn from(value: InteriorNulError) -> Self {
+        Self::InvalidString(value)
+    }
+}
+
+impl From<ManagerError> for DialogError {
+    fn from(value: ManagerError) -> Self {
+        Self::Manager(value)
+    }
+}
+
+impl From<WinError> for DialogError {
+    fn from(value: WinError) -> Self {
+        Self::Win32(value)
+    }
+}
+
+impl From<FromUtf16Error> for DialogError {
+    fn from(value: FromUtf16Error) -> Self {
+        Self::Win32(value.into())
+    }
 }
 
 #[derive(Debug)]
 pub struct FileDialogManager {
     // see https://learn.microsoft.com/en-us/windows/win32/shell/common-file-dialog#controlling-the-default-folder
-    default: PathBuf,
-    window: HWND
+    default_open: PathBuf,
+    default_save: PathBuf,
+    // the paths the manager was created with, for resetting
+    initial_open: PathBuf,
+    initial_save: PathBuf,
+    // remembered paths for caller-defined contexts, see OpenDialog::context
+    open_contexts: HashMap<String, PathBuf>,
+    save_contexts: HashMap<String, PathBuf>,
+    // where remembered folders are saved, see FileDialogManager::with_persistence
+    state_file: Option<PathBuf>,
+    // None until a window is attached for deferred managers. Shared with copies of the manager
+    // so a callback can be called after the global lock is released, see with_unlocked
+    owner: Option<Arc<OwnerSource>>,
+    owner_policy: OwnerPolicy,
+    target_monitor: Option<MonitorSelection>,
+    dedup_filters: bool,
+    confirm_overwrite: bool,
+    completion_notification: CompletionNotification,
+    default_options: DialogOptions,
+    history: FolderHistory,
+    audit_log: AuditLog,
+    client_guid: Option<GUID>,
+    // every client GUID that dialogs have used, for clearing the shell's state
+    client_guids: Vec<GUID>
+}
+
+/// Which layers of remembered state [`FileDialogManager::clear_persisted_state`] clears
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClearScope {
+    /// The manager's default folder and folder history, including pinned folders
+    pub memory: bool,
+    /// The shell's own state for each registered client GUID
+    pub client_data: bool,
+    /// The manager's state file, see [`FileDialogManager::with_persistence`]
+    pub state_file: bool
+}
+
+impl ClearScope {
+    pub const ALL: Self = Self { memory: true, client_data: true, state_file: true };
+    pub const MEMORY: Self = Self { memory: true, client_data: false, state_file: false };
+    pub const CLIENT_DATA: Self = Self { memory: false, client_data: true, state_file: false };
+    pub const STATE_FILE: Self = Self { memory: false, client_data: false, state_file: true };
+}
+
+#[derive(Debug, Default)]
+pub struct ClearReport {
+    /// Whether in-memory state was cleared
+    pub memory: bool,
+    /// The result of clearing each registered client GUID's shell state
+    pub client_data: Vec<(GUID, Result<(), DialogError>)>,
+    /// The result of clearing the state file, or `None` if it wasn't in scope or the manager
+    /// doesn't have one
+    pub state_file: Option<std::io::Result<()>>
+}
+
+impl ClearReport {
+    pub fn is_ok(&self) -> bool {
+        self.client_data.iter().all(|(_, r)| r.is_ok()) && self.state_file.as_ref().is_none_or(|r| r.is_ok())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManagerError {
+    /// The manager hasn't been created yet
+    Uninitialized,
+    /// The manager is borrowed, such as while it's showing a dialog
+    InUse
 }
 
+impl Display for ManagerError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uninitialized => write!(f, "File dialog manager hasn't been initialized"),
+            Self::InUse => write!(f, "File dialog manager is in use")
+        }
+    }
+}
+
+impl Error for ManagerError {}
+
 unsafe impl Send for FileDialogManager {}
 unsafe impl Sync for FileDialogManager {}
+// Window handles can be used from any thread, and callbacks are required to be Send and Sync
+unsafe impl Send for OwnerSource {}
+unsafe impl Sync for OwnerSource {}
+// The only non-Send field is the stale window handle, which is only kept for reporting
+unsafe impl Send for DialogError {}
 
 pub(crate) static FILE_DIALOG_MANAGER: Mutex<Option<FileDialogManager>> = Mutex::new(None);
 type MgrBorrow = MutexGuard<'static, Option<FileDialogManager>>;
 
 impl FileDialogManager {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(default: PathBuf, window: HWND) {
-        let mut lock_dlg = FILE_DIALOG_MANAGER.lock().unwrap();
-        *lock_dlg = Some(Self { default, window })
+        Self::new_with_owner(default, OwnerSource::Window(window))
+    }
+
+    pub fn new_with_owner(default: PathBuf, owner: OwnerSource) {
+        Self::set_global(Self::create(default, Some(owner)))
+    }
+
+    /// Initialize the manager with settings that the other constructors don't cover
+    pub fn init(config: ManagerConfig) {
+        Self::set_global(config.build())
+    }
 
+    /// Initialize the manager without an owner window, for when the window doesn't exist yet.
+    /// Call [`FileDialogManager::attach_window`] once it's been created.
+    pub fn new_deferred(default: PathBuf) {
+        Self::set_global(Self::create(default, None))
     }
 
+    fn create(default: PathBuf, owner: Option<OwnerSource>) -> Self {
+        Self {
+            initial_open: default.clone(),
+            initial_save: default.clone(),
+            default_open: default.clone(),
+            default_save: default,
+            open_contexts: HashMap::new(),
+            save_contexts: HashMap::new(),
+            state_file: None,
+            owner: owner.map(Arc::new),
+            owner_policy: OwnerPolicy::default(),
+            target_monitor: None,
+            dedup_filters: true,
+            confirm_overwrite: false,
+            completion_notification: CompletionNotification::default(),
+            default_options: DialogOptions::new(),
+            history: FolderHistory::default(),
+            audit_log: AuditLog::default(),
+            client_guid: None,
+            client_guids: vec![]
+        }
+    }
+
+    fn set_global(manager: Self) {
+        *Self::lock() = Some(manager)
+    }
+
+    // A panic while the manager was borrowed can't leave it in a broken state, so ignore poisoning
+    fn lock() -> MgrBorrow {
+        FILE_DIALOG_MANAGER.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the manager. The lock is held until the returned guard is dropped, including while any
+    /// dialog borrowed from it is shown, so prefer [`FileDialogManager::with_unlocked`] when other
+    /// threads use the manager.
+    ///
+    /// # Panics
+    ///
+    /// If the manager hasn't been initialized. Use [`FileDialogManager::try_get`] to check first.
+    #[track_caller]
     pub fn get() -> MgrBorrow {
-        Self::try_get().unwrap()
+        Self::try_get().expect("File dialog manager hasn't been initialized")
     }
 
     pub fn try_get() -> Option<MgrBorrow> {
-        let file_dlg = FILE_DIALOG_MANAGER.lock().unwrap();
+        let file_dlg = Self::lock();
         match file_dlg.as_ref().is_some() {
             true => Some(file_dlg),
             false => None
@@ -95,43 +509,347 @@ impl FileDialogManager {
     }
 
     pub fn get_or_set(default: PathBuf, window: HWND) -> MgrBorrow {
-        Self::try_get().unwrap_or_else(|| {
-            Self::new(default, window);
-            Self::get()
-        })
+        let mut file_dlg = Self::lock();
+        if file_dlg.is_none() {
+            *file_dlg = Some(Self::create(default, Some(OwnerSource::Window(window))));
+        }
+        file_dlg
+    }
+
+    /// Run a function with the manager borrowed. The lock is only held until the function returns.
+    pub fn with<F, R>(f: F) -> Result<R, ManagerError>
+    where F: FnOnce(&mut FileDialogManager) -> R {
+        Self::lock().as_mut().map(f).ok_or(ManagerError::Uninitialized)
+    }
+
+    /// Read-only version of [`FileDialogManager::with`]
+    pub fn with_ref<F, R>(f: F) -> Result<R, ManagerError>
+    where F: FnOnce(&FileDialogManager) -> R {
+        Self::lock().as_ref().map(f).ok_or(ManagerError::Uninitialized)
+    }
+
+    pub fn get_default_open(&self) -> &Path { self.default_open.as_path() }
+    pub fn get_default_save(&self) -> &Path { self.default_save.as_path() }
+    pub fn set_default_open<P>(&mut self, value: P) where P: AsRef<Path> { self.default_open = value.as_ref().to_owned() }
+    pub fn set_default_save<P>(&mut self, value: P) where P: AsRef<Path> { self.default_save = value.as_ref().to_owned() }
+    /// The path remembered for open dialogs in a context, or the default if nothing has been
+    /// opened in it yet
+    pub fn get_default_open_for(&self, key: &str) -> &Path {
+        self.open_contexts.get(key).map_or(self.get_default_open(), PathBuf::as_path)
+    }
+    /// The path remembered for save dialogs in a context, or the default if nothing has been
+    /// saved in it yet
+    pub fn get_default_save_for(&self, key: &str) -> &Path {
+        self.save_contexts.get(key).map_or(self.get_default_save(), PathBuf::as_path)
+    }
+    pub fn set_default_open_for<S, P>(&mut self, key: S, value: P) where S: Into<String>, P: AsRef<Path> {
+        self.open_contexts.insert(key.into(), value.as_ref().to_owned());
+    }
+    pub fn set_default_save_for<S, P>(&mut self, key: S, value: P) where S: Into<String>, P: AsRef<Path> {
+        self.save_contexts.insert(key.into(), value.as_ref().to_owned());
     }
+    pub fn get_owner(&self) -> Option<&OwnerSource> { self.owner.as_deref() }
+    pub fn set_owner(&mut self, owner: OwnerSource) { self.owner = Some(Arc::new(owner)) }
+    pub fn attach_window(&mut self, window: HWND) { self.set_owner(OwnerSource::Window(window)) }
+    pub fn get_owner_policy(&self) -> OwnerPolicy { self.owner_policy }
+    pub fn set_owner_policy(&mut self, value: OwnerPolicy) { self.owner_policy = value }
+    pub fn get_target_monitor(&self) -> Option<MonitorSelection> { self.target_monitor }
+    /// Move dialogs to a particular monitor once they're shown. `None` leaves the placement to the shell.
+    pub fn set_target_monitor(&mut self, value: Option<MonitorSelection>) { self.target_monitor = value }
+    pub fn get_dedup_filters(&self) -> bool { self.dedup_filters }
+    /// Remove filters that match the same files as an earlier filter in the list. Enabled by default.
+    pub fn set_dedup_filters(&mut self, value: bool) { self.dedup_filters = value }
+    pub fn get_confirm_overwrite(&self) -> bool { self.confirm_overwrite }
+    /// Replace the shell's overwrite prompt in save dialogs with [`confirm_overwrite`], which can
+    /// also keep both files. Disabled by default.
+    pub fn set_confirm_overwrite(&mut self, value: bool) { self.confirm_overwrite = value }
+    pub fn get_completion_notification(&self) -> CompletionNotification { self.completion_notification }
+    /// Flash the owner window and/or post it a message whenever a dialog closes
+    pub fn set_completion_notification(&mut self, value: CompletionNotification) { self.completion_notification = value }
+    pub fn get_default_options(&self) -> DialogOptions { self.default_options }
+    /// Options applied to every dialog before the crate's defaults for the kind of dialog and the
+    /// options given for a call, which both take precedence over these
+    pub fn set_default_options(&mut self, value: DialogOptions) { self.default_options = value }
+    pub fn get_window_handle(&self) -> Option<HWND> { self.owner.as_deref().and_then(|o| o.resolve()) }
 
-    pub fn get_default_open(&self) -> &Path { self.default.as_path() }
-    pub fn get_default_save(&self) -> &Path { self.default.as_path() }
-    pub fn set_default_open<P>(&mut self, value: P) where P: AsRef<Path> { self.default = value.as_ref().to_owned() }
-    pub fn set_default_save<P>(&mut self, value: P) where P: AsRef<Path> { self.default = value.as_ref().to_owned() }
-    pub fn get_window_handle(&self) -> HWND { self.window }
+    pub fn get_history(&self) -> &FolderHistory { &self.history }
+    pub fn get_history_mut(&mut self) -> &mut FolderHistory { &mut self.history }
+    pub fn set_history(&mut self, value: FolderHistory) { self.history = value }
+    pub fn pin_folder<P>(&mut self, folder: P) where P: AsRef<Path> { self.history.pin(folder) }
+    pub fn unpin_folder<P>(&mut self, folder: P) -> bool where P: AsRef<Path> { self.history.unpin(folder) }
+    pub fn get_pinned_folders(&self) -> &[PathBuf] { self.history.get_pinned() }
+    pub fn get_recent_folders(&self) -> Vec<PathBuf> { self.history.get_recent().iter().cloned().collect() }
+
+    /// The most recent dialogs shown through this manager, oldest first
+    pub fn audit_log(&self) -> Vec<AuditEntry> { self.audit_log.get_entries().iter().cloned().collect() }
+    pub fn get_audit_log(&self) -> &AuditLog { &self.audit_log }
+    pub fn get_audit_log_mut(&mut self) -> &mut AuditLog { &mut self.audit_log }
+
+    pub fn get_client_guid(&self) -> Option<GUID> { self.client_guid }
+    /// Set the GUID that the shell uses to remember state such as the last visited folder for
+    /// dialogs. See `IFileDialog::SetClientGuid`.
+    pub fn set_client_guid(&mut self, value: Option<GUID>) {
+        if let Some(guid) = value {
+            self.register_client_guid(guid);
+        }
+        self.client_guid = value;
+    }
+    /// Add a client GUID to the set that [`FileDialogManager::clear_persisted_state`] clears
+    pub fn register_client_guid(&mut self, guid: GUID) {
+        if !self.client_guids.contains(&guid) {
+            self.client_guids.push(guid);
+        }
+    }
+    pub fn get_client_guids(&self) -> &[GUID] { &self.client_guids }
+
+    /// Forget remembered dialog locations. Each selected layer is cleared independently, and the
+    /// result for each one is returned. The state file is rewritten with the cleared folders when
+    /// memory is cleared too, otherwise it's deleted. A later selection saves it again either way.
+    pub fn clear_persisted_state(&mut self, scope: ClearScope) -> ClearReport {
+        let mut report = ClearReport::default();
+        if scope.memory {
+            self.default_open = self.initial_open.clone();
+            self.default_save = self.initial_save.clone();
+            self.open_contexts.clear();
+            self.save_contexts.clear();
+            self.history.clear();
+            report.memory = true;
+        }
+        if scope.client_data {
+            report.client_data = self.client_guids.iter().map(|guid| {
+                let result = (|| -> Result<(), WinError> {
+                    let dialog: IFileOpenDialog = unsafe { CoCreateInstance(&FileOpenDialog, None, CLSCTX_ALL)? };
+                    unsafe { dialog.SetClientGuid(guid)? };
+                    unsafe { dialog.ClearClientData() }
+                })();
+                if let Err(e) = &result {
+                    log::warn!("Couldn't clear shell state for client {:?}: {}", guid, e);
+                }
+                (*guid, result.map_err(DialogError::from))
+            }).collect();
+        }
+        if scope.state_file && let Some(file) = self.state_file.as_ref() {
+            let result = match scope.memory {
+                true => self.save_state(),
+                false => std::fs::remove_file(file).or_else(|e| match e.kind() {
+                    std::io::ErrorKind::NotFound => Ok(()),
+                    _ => Err(e)
+                })
+            };
+            if let Err(e) = &result {
+                log::warn!("Couldn't clear dialog state in {}: {}", file.display(), e);
+            }
+            report.state_file = Some(result);
+        }
+        report
+    }
+
+    /// Get the window that the next dialog should be parented to, following the manager's
+    /// [`OwnerPolicy`] if no window has been attached yet or the attached window is invalid.
+    pub fn resolve_owner(&self) -> Result<OwnerResolution, DialogError> {
+        let window = match (self.owner.as_deref(), self.owner_policy) {
+            (Some(owner), _) => owner.resolve(),
+            (None, OwnerPolicy::Unowned) => return Ok(OwnerResolution::Unowned),
+            (None, OwnerPolicy::Require) => return Err(DialogError::NoOwnerWindow)
+        };
+        self.check_owner(window)
+    }
+
+    /// Like [`FileDialogManager::resolve_owner`], but for a window given for one dialog instead
+    /// of the manager's owner
+    pub fn resolve_owner_override(&self, window: HWND) -> Result<OwnerResolution, DialogError> {
+        self.check_owner(Some(window))
+    }
+
+    fn check_owner(&self, window: Option<HWND>) -> Result<OwnerResolution, DialogError> {
+        match window {
+            Some(hwnd) if FileDialogUtils::is_valid_owner(hwnd) => Ok(OwnerResolution::Owned(hwnd)),
+            Some(hwnd) => match self.owner_policy {
+                OwnerPolicy::Require => Err(DialogError::StaleWindowHandle(hwnd)),
+                OwnerPolicy::Unowned => {
+                    log::warn!("Owner window {:?} is no longer valid, showing dialog unowned", hwnd.0);
+                    Ok(OwnerResolution::Stale(hwnd))
+                }
+            },
+            None => Ok(OwnerResolution::Unowned)
+        }
+    }
 }
 
 pub trait FileDialog {
     fn get_default_title(&self) -> &'static str;
-    fn get_title(&self, title: Option<&str>) -> Vec<u16> {
-        match title {
-            Some(v) => FileDialogUtils::to_win32_wide(v),
-            None => FileDialogUtils::to_win32_wide(self.get_default_title())
-        }
+    fn get_title(&self, title: Option<&str>) -> Result<Vec<u16>, InteriorNulError> {
+        wide::to_wide(title.unwrap_or(self.get_default_title()))
     }
     fn get_default_path(&self) -> &Path;
     fn set_default_path<P>(&mut self, file: P) where P: AsRef<Path>;
-    fn get_window_handle(&self) -> HWND;
+    fn resolve_owner(&self) -> Result<OwnerResolution, DialogError>;
+    fn get_target_monitor(&self) -> Option<MonitorSelection>;
+    fn get_manager(&self) -> &FileDialogManager;
+    /// Combine the dialog's defaults with the arguments for a call
+    fn resolve_config(&self, kind: DialogKind, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> ResolvedConfig {
+        let (filters, removed_filters) = ResolvedConfig::resolve_filters(filter, self.get_manager().get_dedup_filters());
+        let confirm_overwrite = kind == DialogKind::Save && self.get_manager().get_confirm_overwrite();
+        let (initial_folder, initial_folder_source) = InitialFolderSource::from_environment(kind)
+            .unwrap_or_else(|| (self.get_default_path().to_owned(), InitialFolderSource::ManagerDefault));
+        // Games often load files relative to the working directory, so never let the dialog change it
+        let mut preset = DialogOptions::new().no_change_dir(true);
+        if matches!(kind, DialogKind::OpenFolder | DialogKind::Drive) {
+            preset = preset.flag(DialogFlags::PICK_FOLDERS, true);
+        }
+        if confirm_overwrite {
+            preset = preset.flag(DialogFlags::OVERWRITE_PROMPT, false);
+        }
+        let mut config = ResolvedConfig {
+            kind,
+            title: title.unwrap_or(self.get_default_title()).to_owned(),
+            initial_folder,
+            initial_folder_source,
+            filters,
+            removed_filters,
+            options: FILEOPENDIALOGOPTIONS(0),
+            options_cleared: FILEOPENDIALOGOPTIONS(0),
+            option_layers: vec![],
+            confirm_overwrite,
+            suggested_name: None,
+            save_as_item: None,
+            default_extension: None,
+            filter_index: None,
+            name_pattern: None,
+            verify_writable: false,
+            collect_metadata: false,
+            remember_selection: true,
+            max_file_size: None,
+            name_rule: None,
+            path_style: PathStyle::Native,
+            folder_access: None,
+            client_guid: self.get_manager().get_client_guid(),
+            places: self.get_manager().get_history().get_place_folders()
+        };
+        config.add_layer(OptionLayer::Manager, &self.get_manager().get_default_options());
+        config.add_layer(OptionLayer::Preset, &preset);
+        config
+    }
 }
 pub struct FileDialogUtils;
 impl FileDialogUtils {
-    pub(crate) fn to_win32_wide(s: &str) -> Vec<u16> {
-        let mut alloc = Vec::with_capacity(s.len() + 1);
-        alloc.extend(s.encode_utf16());
-        alloc.push(0); // add null terminator
-        alloc
+    /// Whether an HRESULT from `IModalWindow::Show` means that the user closed the dialog
+    /// without making a selection, rather than that it failed
+    pub fn is_cancelled(code: HRESULT) -> bool {
+        code == ERROR_CANCELLED.to_hresult()
+    }
+
+    /// Get the system's description of an HRESULT in the user's language, or in US English if
+    /// there isn't a translation available.
+    pub fn format_hresult(code: HRESULT) -> Option<String> {
+        const LANG_USER_DEFAULT: u32 = 0;
+        const LANG_EN_US: u32 = 0x409;
+        [LANG_USER_DEFAULT, LANG_EN_US].into_iter().find_map(|lang| Self::format_message(code.0 as u32, lang))
+    }
+
+    fn format_message(code: u32, language: u32) -> Option<String> {
+        // With FORMAT_MESSAGE_ALLOCATE_BUFFER, lpBuffer receives a pointer to a LocalAlloc'd buffer
+        let mut buffer = PWSTR::null();
+        let len = unsafe { FormatMessageW(
+            FORMAT_MESSAGE_ALLOCATE_BUFFER | FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            None, code, language, PWSTR(&raw mut buffer.0 as _), 0, None
+        ) };
+        if buffer.is_null() {
+            return None;
+        }
+        let message = String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(buffer.0, len as usize) });
+        unsafe { LocalFree(Some(HLOCAL(buffer.0 as _))) };
+        // System messages end with a line break
+        let message = message.trim_end();
+        match message.is_empty() {
+            true => None,
+            false => Some(message.to_owned())
+        }
+    }
+
+    /// Check that a file can be written at `path` without changing it. An existing file is opened
+    /// for writing, otherwise an empty file is created and removed again.
+    pub fn probe_writable(path: &Path) -> std::io::Result<()> {
+        match path.exists() {
+            true => std::fs::OpenOptions::new().append(true).open(path).map(|_| ()),
+            false => {
+                std::fs::OpenOptions::new().write(true).create_new(true).open(path)?;
+                std::fs::remove_file(path)
+            }
+        }
+    }
+
+    /// Check that a folder's contents can be listed and, if `write` is set, that files can be
+    /// created in it. The file created for the write check is removed again.
+    pub fn probe_folder(path: &Path, write: bool) -> std::io::Result<()> {
+        std::fs::read_dir(path)?.next().transpose()?;
+        if write {
+            let probe = (0..).map(|i| path.join(format!(".riri-probe-{}-{}", std::process::id(), i)))
+                .find(|p| !p.exists())
+                .unwrap();
+            std::fs::OpenOptions::new().write(true).create_new(true).open(&probe)?;
+            std::fs::remove_file(&probe)?;
+        }
+        Ok(())
+    }
+
+    /// Find a visible top-level window owned by the current process. The foreground window is
+    /// used if it belongs to this process, otherwise the first one found in Z order.
+    pub fn find_process_window() -> Option<HWND> {
+        let process = unsafe { GetCurrentProcessId() };
+        let foreground = unsafe { GetForegroundWindow() };
+        if Self::is_process_window(foreground, process) {
+            return Some(foreground);
+        }
+        let mut search = ProcessWindowSearch { process, window: None };
+        // EnumWindows reports an error when the callback stops enumeration early
+        let _ = unsafe { EnumWindows(Some(find_process_window_proc), LPARAM(&raw mut search as isize)) };
+        search.window
+    }
+
+    /// Check that a window still exists and belongs to this process
+    pub fn is_valid_owner(window: HWND) -> bool {
+        if !unsafe { IsWindow(Some(window)) }.as_bool() {
+            return false;
+        }
+        let mut window_process = 0;
+        unsafe { GetWindowThreadProcessId(window, Some(&mut window_process)) };
+        window_process == unsafe { GetCurrentProcessId() }
+    }
+
+    fn is_process_window(window: HWND, process: u32) -> bool {
+        if window.is_invalid() {
+            return false;
+        }
+        let mut window_process = 0;
+        unsafe { GetWindowThreadProcessId(window, Some(&mut window_process)) };
+        window_process == process && unsafe { IsWindowVisible(window).as_bool() }
+    }
+}
+
+struct ProcessWindowSearch {
+    process: u32,
+    window: Option<HWND>
+}
+
+unsafe extern "system" fn find_process_window_proc(window: HWND, param: LPARAM) -> BOOL {
+    let search = unsafe { &mut *(param.0 as *mut ProcessWindowSearch) };
+    match FileDialogUtils::is_process_window(window, search.process) {
+        true => {
+            search.window = Some(window);
+            false.into()
+        },
+        false => true.into()
     }
 }
 pub struct OpenDialog<'a> {
     manager: &'a mut FileDialogManager,
-    handle: IFileOpenDialog
+    handle: IFileOpenDialog,
+    owner: Option<OwnerResolution>,
+    settings: CallSettings,
+    selection_info: Option<SelectionInfo>,
+    styled: Vec<StyledPath>,
+    selected_filter: Option<usize>
 }
 impl<'a> FileDialog for OpenDialog<'a> {
     fn get_default_title(&self) -> &'static str {
@@ -139,72 +857,255 @@ impl<'a> FileDialog for OpenDialog<'a> {
     }
 
     fn get_default_path(&self) -> &Path {
-        self.manager.get_default_open()
+        match self.settings.context.as_deref() {
+            Some(key) => self.manager.get_default_open_for(key),
+            None => self.manager.get_default_open()
+        }
     }
 
     fn set_default_path<P>(&mut self, file: P) where P: AsRef<Path> {
-        self.manager.set_default_open(file)
+        match self.settings.context.clone() {
+            Some(key) => self.manager.set_default_open_for(key, file),
+            None => self.manager.set_default_open(file)
+        }
+    }
+
+    fn resolve_owner(&self) -> Result<OwnerResolution, DialogError> {
+        match self.settings.owner {
+            Some(window) => self.manager.resolve_owner_override(window),
+            None => self.manager.resolve_owner()
+        }
+    }
+
+    fn get_target_monitor(&self) -> Option<MonitorSelection> {
+        self.manager.get_target_monitor()
+    }
+
+    fn get_manager(&self) -> &FileDialogManager {
+        self.manager
+    }
+}
+
+impl<'a> DialogPipeline for OpenDialog<'a> {
+    fn get_handle(&self) -> &IFileDialog {
+        &self.handle
+    }
+
+    fn get_manager_mut(&mut self) -> &mut FileDialogManager {
+        self.manager
+    }
+
+    fn set_owner_resolution(&mut self, value: OwnerResolution) {
+        self.owner = Some(value)
+    }
+
+    fn set_selection_info(&mut self, value: Option<SelectionInfo>) {
+        self.selection_info = value
     }
 
-    fn get_window_handle(&self) -> HWND {
-        self.manager.get_window_handle()
+    fn set_styled_results(&mut self, value: Vec<StyledPath>) {
+        self.styled = value
+    }
+
+    fn set_selected_filter(&mut self, value: Option<usize>) {
+        self.selected_filter = value
     }
 }
 
 impl<'a> OpenDialog<'a> {
     pub fn new(manager: &'a mut FileDialogManager) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::create(manager)?)
+    }
+
+    // public for define_dialog!, which returns DialogError instead of a boxed error
+    #[doc(hidden)]
+    pub fn create(manager: &'a mut FileDialogManager) -> Result<Self, WinError> {
         Ok(Self {
             manager,
-            handle: unsafe { CoCreateInstance(&FileOpenDialog, None, CLSCTX_ALL)? }
+            handle: unsafe { CoCreateInstance(&FileOpenDialog, None, CLSCTX_ALL)? },
+            owner: None,
+            settings: CallSettings::default(),
+            selection_info: None,
+            styled: vec![],
+            selected_filter: None
         })
     }
 
-    fn open_inner(&mut self, title: Option<&str>) -> Result<Option<PathBuf>, WinError> {
-        // Window Title
-        let title = self.get_title(title);
-        unsafe { self.handle.SetTitle(PCWSTR(title.as_ptr()))? }
-        // Default folder
-        let default_folder = FileDialogUtils::to_win32_wide(self.get_default_path().to_str().unwrap());
-        let item: IShellItem = unsafe { SHCreateItemFromParsingName(PCWSTR(default_folder.as_ptr()), None)? };
-        unsafe { self.handle.SetDefaultFolder(&item)? };
-        // Run open dialog
-        if unsafe { self.handle.Show(Some(self.get_window_handle())).is_ok() } {
-            let res = unsafe { self.handle.GetResult()? };
-            let path = unsafe { res.GetDisplayName(SIGDN_FILESYSPATH)? };
-            let out = PathBuf::from(unsafe { path.to_string()? });
-            self.set_default_path(out.as_path());
-            unsafe { CoTaskMemFree(Some(path.0 as _)) }
-            Ok(Some(out))
-        } else {
-            Ok(None)
-        }
-    }
-
-    pub fn open(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<PathBuf>, WinError> {
-        // Provide owned allocation for file type strings
-        let filter_platform: Option<Vec<FileTypeFilterWin32>> = filter.map(|filter| {
-            filter.iter().map(|v| FileTypeFilterWin32::new(v.get_extension(), v.get_description())).collect()
-        });
-        if let Some(f) = filter_platform {
-            let types: Vec<COMDLG_FILTERSPEC> = f.iter().map(|v| COMDLG_FILTERSPEC {
-                pszName: v.get_description(),
-                pszSpec: v.get_extension()
-            }).collect();
-            unsafe { self.handle.SetFileTypes(types.as_slice())? };
-        }
-        self.open_inner(title)
+    /// Parent this dialog to a different window than the manager's owner, such as a tool window
+    /// that asked for the dialog. The manager's owner isn't changed.
+    pub fn owner(mut self, window: HWND) -> Self {
+        self.settings.owner = Some(window);
+        self
+    }
+
+    /// Start in the folder remembered for a context such as `"textures"`, and remember the
+    /// selection there instead of in the manager's default. Unknown contexts start in the default.
+    pub fn context<S>(mut self, key: S) -> Self where S: Into<String> {
+        self.settings.context = Some(key.into());
+        self
+    }
+
+    /// Change this dialog's flags on top of the defaults. Later calls take precedence.
+    pub fn with_options(mut self, options: DialogOptions) -> Self {
+        self.settings.options = self.settings.options.merge(options);
+        self
+    }
+
+    /// Have the dialog refuse files with the read-only attribute, see [`DialogOptions::no_readonly_return`]
+    pub fn refuse_read_only(mut self) -> Self {
+        self.settings.options = self.settings.options.no_readonly_return(true);
+        self
+    }
+
+    /// Collect a [`SelectionInfo`] for the selection, such as whether it's read-only, so callers
+    /// can warn instead of refusing it
+    pub fn collect_metadata(mut self) -> Self {
+        self.settings.collect_metadata = true;
+        self
+    }
+
+    /// Add a folder to the navigation pane for this dialog
+    pub fn add_place<P>(mut self, folder: P) -> Self where P: Into<PathBuf> {
+        self.settings.places.push(folder.into());
+        self
+    }
+
+    /// Strip the navigation pane down for kiosk-style use. Recent and pinned places are hidden and
+    /// folders from the manager's history aren't added, so only places from
+    /// [`add_place`](Self::add_place) are shown.
+    pub fn minimal_chrome(mut self) -> Self {
+        self.settings.options = self.settings.options.minimal_chrome();
+        self.settings.minimal_chrome = true;
+        self
+    }
+
+    /// Refuse selections whose file name doesn't match `pattern`, showing `message` to the user.
+    /// Names typed into a save dialog are checked the same way.
+    pub fn require_name_matches<S>(mut self, pattern: NamePattern, message: S) -> Self where S: Into<String> {
+        self.settings.name_rule = Some((pattern, message.into()));
+        self
+    }
+
+    /// Also write selected paths in another style, such as with forward slashes for config
+    /// files. Get them with [`get_styled_results`](Self::get_styled_results).
+    pub fn path_style(mut self, style: PathStyle) -> Self {
+        self.settings.path_style = style;
+        self
+    }
+
+    /// The last selection in the style from [`path_style`](Self::path_style), alongside the
+    /// native path. Empty if the dialog was cancelled or no style was set.
+    pub fn get_styled_results(&self) -> &[StyledPath] { &self.styled }
+
+    /// Details about the last selection, if [`collect_metadata`](Self::collect_metadata) was used
+    pub fn get_selection_info(&self) -> Option<&SelectionInfo> { self.selection_info.as_ref() }
+
+    /// Index into the caller's filters of the filter that was selected when the user accepted
+    /// the last selection. `None` if no filters were given.
+    pub fn get_selected_filter(&self) -> Option<usize> { self.selected_filter }
+
+    /// Select one of the filters when the dialog opens, by its index in the list passed to the
+    /// dialog
+    pub fn filter_index(mut self, index: usize) -> Self {
+        self.settings.filter_index = Some(index);
+        self
+    }
+
+    /// The owner window that the dialog was last shown with
+    pub fn get_owner_resolution(&self) -> Option<OwnerResolution> { self.owner }
+
+    /// The options currently set on the underlying dialog, including any set by the shell
+    pub fn options(&self) -> Result<DialogFlags, DialogError> {
+        Ok(pipeline::get_options(&self.handle)?)
+    }
+
+    /// Put a wildcard pattern such as `character_*.pak` in the file name box, so that only
+    /// matching files are listed. If the user accepts the pattern itself instead of choosing a
+    /// file, `policy` decides what happens.
+    pub fn name_pattern<S>(mut self, pattern: S, policy: PatternPolicy) -> Self where S: Into<String> {
+        self.settings.name_pattern = Some((pattern.into(), policy));
+        self
     }
 
-    pub fn open_folder(&mut self, title: Option<&str>) -> Result<Option<PathBuf>, WinError> {
-        let options = unsafe { self.handle.GetOptions()? };
-        unsafe { self.handle.SetOptions(options | FOS_PICKFOLDERS)? };
-        self.open_inner(title)
+    /// Refuse files larger than `bytes` when the user presses OK, naming the file and the limit.
+    /// The size is checked again after the dialog closes, returning [`DialogError::FileTooLarge`].
+    pub fn max_file_size(mut self, bytes: u64) -> Self {
+        self.settings.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Refuse folders that can't be listed, or also written to with [`FolderAccess::ReadWrite`],
+    /// when the user presses OK. The folder is checked again after the dialog closes, returning
+    /// [`DialogError::FolderNotAccessible`]. Files aren't checked.
+    pub fn require_folder_access(mut self, access: FolderAccess) -> Self {
+        self.settings.folder_access = Some(access);
+        self
+    }
+
+    /// Get the configuration that [`OpenDialog::open`] would use, without showing the dialog
+    pub fn resolve(&self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> ResolvedConfig {
+        let mut config = self.resolve_config(DialogKind::Open, filter, title);
+        self.settings.apply(&mut config);
+        config
+    }
+
+    /// Get the configuration that [`OpenDialog::open_folder`] would use, without showing the dialog
+    pub fn resolve_folder(&self, title: Option<&str>) -> ResolvedConfig {
+        let mut config = self.resolve_config(DialogKind::OpenFolder, None, title);
+        self.settings.apply(&mut config);
+        config
+    }
+
+    pub fn open(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<PathBuf>, DialogError> {
+        self.open_with(filter, title, &DialogOptions::default())
+    }
+
+    /// Open a file with flag changes and a client GUID for this call only, on top of any set
+    /// with [`with_options`](Self::with_options)
+    pub fn open_with(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>, options: &DialogOptions)
+        -> Result<Option<PathBuf>, DialogError> {
+        let mut config = self.resolve(filter, title);
+        config.add_options(options);
+        self.run(&config, DialogEvents::new())
+    }
+
+    /// Like [`OpenDialog::open`], but also returns which filter was selected
+    pub fn open_with_selection(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<DialogSelection>, DialogError> {
+        let path = self.open(filter, title)?;
+        Ok(path.map(|path| DialogSelection { path, filter_index: self.selected_filter }))
+    }
+
+    /// Let the user select any number of files. Cancelling returns `None`, while the shell
+    /// reporting an empty selection returns an empty list. The folder of the first file is
+    /// remembered.
+    pub fn open_multiple(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<Vec<PathBuf>>, DialogError> {
+        let mut config = self.resolve(filter, title);
+        config.add_layer(OptionLayer::Preset, &DialogOptions::new().flag(DialogFlags::ALLOW_MULTISELECT, true));
+        self.run_multiple(&config, DialogEvents::new())
+    }
+
+    /// Open a file with filters generated from a list of extensions, see
+    /// [`FileTypeFilter::for_extensions`]
+    pub fn open_with_extensions(&mut self, extensions: &[&str], grouping: ExtensionGrouping, title: Option<&str>)
+        -> Result<Option<PathBuf>, DialogError> {
+        let filters = FileTypeFilter::for_extensions(extensions, grouping);
+        self.open((!filters.is_empty()).then_some(&filters), title)
+    }
+
+    pub fn open_folder(&mut self, title: Option<&str>) -> Result<Option<PathBuf>, DialogError> {
+        let config = self.resolve_folder(title);
+        self.run(&config, DialogEvents::new())
     }
 }
 
 pub struct SaveDialog<'a> {
     manager: &'a mut FileDialogManager,
-    handle: IFileSaveDialog
+    handle: IFileSaveDialog,
+    owner: Option<OwnerResolution>,
+    settings: CallSettings,
+    selection_info: Option<SelectionInfo>,
+    styled: Vec<StyledPath>,
+    selected_filter: Option<usize>
 }
 impl<'a> FileDialog for SaveDialog<'a> {
     fn get_default_title(&self) -> &'static str {
@@ -212,55 +1113,319 @@ impl<'a> FileDialog for SaveDialog<'a> {
     }
 
     fn get_default_path(&self) -> &Path {
-        self.manager.get_default_save()
+        match self.settings.context.as_deref() {
+            Some(key) => self.manager.get_default_save_for(key),
+            None => self.manager.get_default_save()
+        }
     }
 
     fn set_default_path<P>(&mut self, file: P) where P: AsRef<Path> {
-        self.manager.set_default_save(file);
+        match self.settings.context.clone() {
+            Some(key) => self.manager.set_default_save_for(key, file),
+            None => self.manager.set_default_save(file)
+        }
+    }
+
+    fn resolve_owner(&self) -> Result<OwnerResolution, DialogError> {
+        match self.settings.owner {
+            Some(window) => self.manager.resolve_owner_override(window),
+            None => self.manager.resolve_owner()
+        }
+    }
+
+    fn get_target_monitor(&self) -> Option<MonitorSelection> {
+        self.manager.get_target_monitor()
+    }
+
+    fn get_manager(&self) -> &FileDialogManager {
+        self.manager
+    }
+}
+
+impl<'a> DialogPipeline for SaveDialog<'a> {
+    fn get_handle(&self) -> &IFileDialog {
+        &self.handle
+    }
+
+    fn get_manager_mut(&mut self) -> &mut FileDialogManager {
+        self.manager
+    }
+
+    fn set_owner_resolution(&mut self, value: OwnerResolution) {
+        self.owner = Some(value)
+    }
+
+    fn set_selection_info(&mut self, value: Option<SelectionInfo>) {
+        self.selection_info = value
     }
 
-    fn get_window_handle(&self) -> HWND {
-        self.manager.get_window_handle()
+    fn set_styled_results(&mut self, value: Vec<StyledPath>) {
+        self.styled = value
+    }
+
+    fn set_selected_filter(&mut self, value: Option<usize>) {
+        self.selected_filter = value
     }
 }
 
 impl<'a> SaveDialog<'a> {
     pub fn new(manager: &'a mut FileDialogManager) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::create(manager)?)
+    }
+
+    // public for define_dialog!, which returns DialogError instead of a boxed error
+    #[doc(hidden)]
+    pub fn create(manager: &'a mut FileDialogManager) -> Result<Self, WinError> {
         Ok(Self {
             manager,
-            handle: unsafe { CoCreateInstance(&FileSaveDialog, None, CLSCTX_ALL)? }
+            handle: unsafe { CoCreateInstance(&FileSaveDialog, None, CLSCTX_ALL)? },
+            owner: None,
+            settings: CallSettings::default(),
+            selection_info: None,
+            styled: vec![],
+            selected_filter: None
         })
     }
 
-    pub fn save(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<PathBuf>, WinError> {
-        // Provide owned allocation for file type strings
-        let filter_platform: Option<Vec<FileTypeFilterWin32>> = filter.map(|filter| {
-            filter.iter().map(|v| FileTypeFilterWin32::new(v.get_extension(), v.get_description())).collect()
-        });
-        if let Some(f) = filter_platform {
-            let types: Vec<COMDLG_FILTERSPEC> = f.iter().map(|v| COMDLG_FILTERSPEC {
-                pszName: v.get_description(),
-                pszSpec: v.get_extension()
-            }).collect();
-            unsafe { self.handle.SetFileTypes(types.as_slice())? };
-        }
-        // Window Title
-        let title = self.get_title(title);
-        unsafe { self.handle.SetTitle(PCWSTR(title.as_ptr()))? }
-        // Default folder
-        let default_folder = FileDialogUtils::to_win32_wide(self.get_default_path().to_str().unwrap());
-        let item: IShellItem = unsafe { SHCreateItemFromParsingName(PCWSTR(default_folder.as_ptr()), None)? };
-        unsafe { self.handle.SetDefaultFolder(&item)? };
-        // Run open dialog
-        if unsafe { self.handle.Show(Some(self.get_window_handle())).is_ok() } {
-            let res = unsafe { self.handle.GetResult()? };
-            let path = unsafe { res.GetDisplayName(SIGDN_FILESYSPATH)? };
-            let out =  PathBuf::from(unsafe { path.to_string()? });
-            self.set_default_path(out.clone());
-            unsafe { CoTaskMemFree(Some(path.0 as _)) }
-            Ok(Some(out))
-        } else {
-            Ok(None)
-        }
-    }
-}
\ No newline at end of file
+    /// Parent this dialog to a different window than the manager's owner, such as a tool window
+    /// that asked for the dialog. The manager's owner isn't changed.
+    pub fn owner(mut self, window: HWND) -> Self {
+        self.settings.owner = Some(window);
+        self
+    }
+
+    /// Start in the folder remembered for a context such as `"textures"`, and remember the
+    /// selection there instead of in the manager's default. Unknown contexts start in the default.
+    pub fn context<S>(mut self, key: S) -> Self where S: Into<String> {
+        self.settings.context = Some(key.into());
+        self
+    }
+
+    /// Change this dialog's flags on top of the defaults. Later calls take precedence.
+    pub fn with_options(mut self, options: DialogOptions) -> Self {
+        self.settings.options = self.settings.options.merge(options);
+        self
+    }
+
+    /// Have the dialog refuse files with the read-only attribute, see [`DialogOptions::no_readonly_return`]
+    pub fn refuse_read_only(mut self) -> Self {
+        self.settings.options = self.settings.options.no_readonly_return(true);
+        self
+    }
+
+    /// Collect a [`SelectionInfo`] for the selection, such as whether it's read-only, so callers
+    /// can warn instead of refusing it
+    pub fn collect_metadata(mut self) -> Self {
+        self.settings.collect_metadata = true;
+        self
+    }
+
+    /// Add a folder to the navigation pane for this dialog
+    pub fn add_place<P>(mut self, folder: P) -> Self where P: Into<PathBuf> {
+        self.settings.places.push(folder.into());
+        self
+    }
+
+    /// Strip the navigation pane down for kiosk-style use. Recent and pinned places are hidden and
+    /// folders from the manager's history aren't added, so only places from
+    /// [`add_place`](Self::add_place) are shown.
+    pub fn minimal_chrome(mut self) -> Self {
+        self.settings.options = self.settings.options.minimal_chrome();
+        self.settings.minimal_chrome = true;
+        self
+    }
+
+    /// Refuse selections whose file name doesn't match `pattern`, showing `message` to the user.
+    /// Names typed into a save dialog are checked the same way.
+    pub fn require_name_matches<S>(mut self, pattern: NamePattern, message: S) -> Self where S: Into<String> {
+        self.settings.name_rule = Some((pattern, message.into()));
+        self
+    }
+
+    /// Also write selected paths in another style, such as with forward slashes for config
+    /// files. Get them with [`get_styled_results`](Self::get_styled_results).
+    pub fn path_style(mut self, style: PathStyle) -> Self {
+        self.settings.path_style = style;
+        self
+    }
+
+    /// The last selection in the style from [`path_style`](Self::path_style), alongside the
+    /// native path. Empty if the dialog was cancelled or no style was set.
+    pub fn get_styled_results(&self) -> &[StyledPath] { &self.styled }
+
+    /// Details about the last selection, if [`collect_metadata`](Self::collect_metadata) was used
+    pub fn get_selection_info(&self) -> Option<&SelectionInfo> { self.selection_info.as_ref() }
+
+    /// Index into the caller's filters of the filter that was selected when the user accepted
+    /// the last selection. `None` if no filters were given.
+    pub fn get_selected_filter(&self) -> Option<usize> { self.selected_filter }
+
+    /// Select one of the filters when the dialog opens, by its index in the list passed to the
+    /// dialog
+    pub fn filter_index(mut self, index: usize) -> Self {
+        self.settings.filter_index = Some(index);
+        self
+    }
+
+    /// The owner window that the dialog was last shown with
+    pub fn get_owner_resolution(&self) -> Option<OwnerResolution> { self.owner }
+
+    /// The options currently set on the underlying dialog, including any set by the shell
+    pub fn options(&self) -> Result<DialogFlags, DialogError> {
+        Ok(pipeline::get_options(&self.handle)?)
+    }
+
+    /// See [`DialogOptions::skip_test_create`]. Pair with [`SaveDialog::verify_writable`] to still
+    /// check the selection, or check it with [`FileDialogUtils::probe_writable`] yourself.
+    pub fn skip_test_create(mut self) -> Self {
+        self.settings.options = self.settings.options.skip_test_create(true);
+        self
+    }
+
+    /// Check that the selected path can be written to before returning it, failing with
+    /// [`DialogError::NotWritable`] if it can't
+    pub fn verify_writable(mut self) -> Self {
+        self.settings.verify_writable = true;
+        self
+    }
+
+    /// Get the configuration that [`SaveDialog::save`] would use, without showing the dialog
+    pub fn resolve(&self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> ResolvedConfig {
+        let mut config = self.resolve_config(DialogKind::Save, filter, title);
+        self.settings.apply(&mut config);
+        config
+    }
+
+    /// Get the configuration that [`SaveDialog::save_as`] would use. Fields can be changed before
+    /// passing it to [`SaveDialog::save_resolved`].
+    pub fn resolve_as<P>(&self, path: P, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> ResolvedConfig
+    where P: AsRef<Path> {
+        let mut config = self.resolve(filter, title);
+        config.suggest_path(&SuggestedPath::decompose(path.as_ref(), filter.unwrap_or_default()));
+        config
+    }
+
+    /// Get the configuration that [`SaveDialog::save_with_options`] would use
+    pub fn resolve_with_options(&self, filter: Option<&[FileTypeFilter]>, options: &SaveOptions) -> ResolvedConfig {
+        let mut config = self.resolve(filter, options.title.as_deref());
+        options.apply(&mut config);
+        config
+    }
+
+    pub fn save(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<PathBuf>, DialogError> {
+        self.save_with(filter, title, &DialogOptions::default())
+    }
+
+    /// Save a file with flag changes and a client GUID for this call only, on top of any set
+    /// with [`with_options`](Self::with_options)
+    pub fn save_with(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>, options: &DialogOptions)
+        -> Result<Option<PathBuf>, DialogError> {
+        let mut config = self.resolve(filter, title);
+        config.add_options(options);
+        self.run(&config, DialogEvents::new())
+    }
+
+    /// Like [`SaveDialog::save`], but also returns which filter was selected, for saving in the
+    /// chosen format regardless of the extension that was typed
+    pub fn save_with_selection(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<DialogSelection>, DialogError> {
+        let path = self.save(filter, title)?;
+        Ok(path.map(|path| DialogSelection { path, filter_index: self.selected_filter }))
+    }
+
+    /// Save with a prefilled file name, default extension or selected file
+    pub fn save_with_options(&mut self, filter: Option<&[FileTypeFilter]>, options: &SaveOptions) -> Result<Option<PathBuf>, DialogError> {
+        let config = self.resolve_with_options(filter, options);
+        self.run(&config, DialogEvents::new())
+    }
+
+    /// Save a file with filters generated from a list of extensions, see
+    /// [`FileTypeFilter::for_extensions`]
+    pub fn save_with_extensions(&mut self, extensions: &[&str], grouping: ExtensionGrouping, title: Option<&str>)
+        -> Result<Option<PathBuf>, DialogError> {
+        let filters = FileTypeFilter::for_extensions(extensions, grouping);
+        self.save((!filters.is_empty()).then_some(&filters), title)
+    }
+
+    /// Save with the folder, file name, extension and filter taken from a suggested target path
+    pub fn save_as<P>(&mut self, path: P, filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Option<PathBuf>, DialogError>
+    where P: AsRef<Path> {
+        let config = self.resolve_as(path, filter, title);
+        self.run(&config, DialogEvents::new())
+    }
+
+    /// Show the dialog with a configuration from [`SaveDialog::resolve`] or [`SaveDialog::resolve_as`]
+    pub fn save_resolved(&mut self, config: &ResolvedConfig) -> Result<Option<PathBuf>, DialogError> {
+        self.run(config, DialogEvents::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ self, ErrorKind };
+    use super::*;
+
+    // Tests that use the global manager take this first, since they'd replace each other's
+    pub(crate) static GLOBAL: Mutex<()> = Mutex::new(());
+
+    fn io_error() -> io::Error {
+        io::Error::new(ErrorKind::PermissionDenied, "access denied")
+    }
+
+    #[test]
+    fn dialog_error_display() {
+        let path = PathBuf::from(r"C:\exports\level3.json");
+        let cases = [
+            (DialogError::NoOwnerWindow, "No owner window has been attached to the file dialog manager".to_owned()),
+            (DialogError::StaleWindowHandle(HWND(0x1234 as _)), "Owner window 0x1234 is no longer valid".to_owned()),
+            (DialogError::InvalidString(InteriorNulError { position: 3 }), "String contains a NUL character at position 3".to_owned()),
+            (DialogError::ConflictingOptions("a and b"), "Conflicting dialog options: a and b".to_owned()),
+            (DialogError::NotWritable { path: path.clone(), source: io_error() }, format!("Can't write to {}: access denied", path.display())),
+            (DialogError::OpenFailed { path: path.clone(), source: io_error() }, format!("Couldn't open {}: access denied", path.display())),
+            (DialogError::FileInUse { path: path.clone(), source: io_error() }, format!("{} is being used by another process", path.display())),
+            (DialogError::NameMismatch { path: path.clone(), message: "Pick a save slot".to_owned() }, "Pick a save slot".to_owned()),
+            (DialogError::FileTooLarge { path: path.clone(), size: 2048, limit: 1024 }, "level3.json is 2048 bytes, which is over the limit of 1024 bytes".to_owned()),
+            (DialogError::FolderNotAccessible { path: path.clone(), source: io_error() }, format!("Can't access {}: access denied", path.display())),
+            (DialogError::PatternSelected(PathBuf::from("*.json")), "No file was chosen, the selection was the pattern *.json".to_owned()),
+            (DialogError::AsyncPending, "Another file dialog is already open".to_owned()),
+            (DialogError::Internal { message: "index out of bounds".to_owned() }, "File dialog failed unexpectedly: index out of bounds".to_owned()),
+            (DialogError::Manager(ManagerError::InUse), "File dialog manager is in use".to_owned()),
+            // the customer bit is set, so the system has no message for this code
+            (DialogError::Win32(WinError::from_hresult(HRESULT(0x2000_0001))), "Windows error 0x20000001".to_owned())
+        ];
+        for (error, expected) in cases {
+            assert_eq!(error.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn manager_error_display() {
+        assert_eq!(ManagerError::Uninitialized.to_string(), "File dialog manager hasn't been initialized");
+        assert_eq!(ManagerError::InUse.to_string(), "File dialog manager is in use");
+    }
+
+    #[test]
+    fn format_hresult_known_codes() {
+        use windows::Win32::Foundation::{ E_ACCESSDENIED, ERROR_FILE_NOT_FOUND };
+        for code in [E_ACCESSDENIED, ERROR_FILE_NOT_FOUND.to_hresult()] {
+            let message = FileDialogUtils::format_hresult(code).unwrap();
+            assert!(!message.is_empty());
+            assert!(!message.ends_with(['\r', '\n']), "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn format_hresult_unknown_code() {
+        assert_eq!(FileDialogUtils::format_hresult(HRESULT(0x2000_0001)), None);
+    }
+
+    #[test]
+    fn cancelled_codes() {
+        use windows::Win32::Foundation::{ E_ACCESSDENIED, E_FAIL, S_OK };
+        // HRESULT_FROM_WIN32(ERROR_CANCELLED), which Show returns when the dialog is closed
+        assert!(FileDialogUtils::is_cancelled(HRESULT(0x800704C7_u32 as i32)));
+        assert!(FileDialogUtils::is_cancelled(ERROR_CANCELLED.to_hresult()));
+        for code in [E_FAIL, E_ACCESSDENIED, S_OK] {
+            assert!(!FileDialogUtils::is_cancelled(code), "{:?}", code);
+        }
+    }
+}
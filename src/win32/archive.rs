@@ -0,0 +1,267 @@
+use std::{
+    path::{ Path, PathBuf },
+    sync::atomic::{ AtomicU32, Ordering }
+};
+use windows::{
+    core::{ HSTRING, PCWSTR },
+    Win32::{
+        System::Com::{ CoCreateInstance, CoTaskMemFree, CLSCTX_ALL },
+        UI::Shell::{
+            BHID_EnumItems,
+            FileOpenDialog,
+            FileOperation,
+            FOF_NO_UI,
+            IEnumShellItems,
+            IFileDialogEvents,
+            IFileOpenDialog,
+            IFileOperation,
+            IShellItem,
+            SHCreateItemFromParsingName,
+            SIGDN,
+            SIGDN_DESKTOPABSOLUTEPARSING,
+            SIGDN_PARENTRELATIVEPARSING
+        }
+    }
+};
+use crate::utils::wide;
+use super::{ DialogError, FileTypeFilter, OpenDialog, events::{ self, DialogEvents }, pipeline };
+
+/// An entry extracted from an archive by [`OpenDialog::open_or_extract`]. The extracted copy is
+/// deleted when this is dropped, unless [`ExtractedSelection::persist`] is called.
+#[derive(Debug)]
+pub struct ExtractedSelection {
+    pub original_archive: PathBuf,
+    /// Path of the entry inside the archive, using `\` between folders
+    pub entry_name: String,
+    pub extracted_path: PathBuf,
+    // the folder created for this extraction, removed on drop
+    folder: PathBuf,
+    keep: bool
+}
+
+impl ExtractedSelection {
+    /// Keep the extracted file after this is dropped, returning its path
+    pub fn persist(mut self) -> PathBuf {
+        self.keep = true;
+        self.extracted_path.clone()
+    }
+}
+
+impl Drop for ExtractedSelection {
+    fn drop(&mut self) {
+        if !self.keep && let Err(e) = std::fs::remove_dir_all(&self.folder) {
+            log::warn!("Couldn't remove extracted files in {}: {}", self.folder.display(), e);
+        }
+    }
+}
+
+/// The result of [`OpenDialog::open_or_extract`]
+#[derive(Debug)]
+pub enum OpenSelection {
+    /// The selection wasn't an archive
+    File(PathBuf),
+    Extracted(ExtractedSelection)
+}
+
+impl OpenSelection {
+    /// The path to read the selection from
+    pub fn get_path(&self) -> &Path {
+        match self {
+            Self::File(p) => p,
+            Self::Extracted(e) => &e.extracted_path
+        }
+    }
+}
+
+// Extension of archives that the shell can browse as folders
+const ARCHIVE_EXTENSIONS: [&str; 1] = ["zip"];
+
+fn is_archive(path: &Path) -> bool {
+    path.extension().is_some_and(|e| ARCHIVE_EXTENSIONS.iter().any(|a| e.eq_ignore_ascii_case(a)))
+}
+
+fn display_name(item: &IShellItem, sigdn: SIGDN) -> Result<String, DialogError> {
+    let name = unsafe { item.GetDisplayName(sigdn)? };
+    let out = unsafe { name.to_string() };
+    unsafe { CoTaskMemFree(Some(name.0 as _)) }
+    Ok(out?)
+}
+
+/// Every file in an archive, with their path inside it. Folders are anything that can be enumerated.
+fn list_entries(folder: &IShellItem, prefix: &str, out: &mut Vec<(String, IShellItem)>) -> Result<(), DialogError> {
+    let items: IEnumShellItems = unsafe { folder.BindToHandler(None, &BHID_EnumItems)? };
+    loop {
+        let mut item = [None];
+        let mut fetched = 0;
+        unsafe { items.Next(&mut item, Some(&mut fetched))? };
+        let Some(item) = item[0].take().filter(|_| fetched == 1) else {
+            return Ok(());
+        };
+        let name = format!("{}{}", prefix, display_name(&item, SIGDN_PARENTRELATIVEPARSING)?);
+        match unsafe { item.BindToHandler::<_, IEnumShellItems>(None, &BHID_EnumItems) } {
+            Ok(_) => list_entries(&item, &format!("{}\\", name), out)?,
+            Err(_) => out.push((name, item))
+        }
+    }
+}
+
+/// Copy an item out of an archive into a new folder inside `temp_dir`
+fn extract(item: &IShellItem, temp_dir: &Path) -> Result<PathBuf, DialogError> {
+    static EXTRACTIONS: AtomicU32 = AtomicU32::new(0);
+    let folder = temp_dir.join(format!("riri-extract-{}-{}", std::process::id(), EXTRACTIONS.fetch_add(1, Ordering::Relaxed)));
+    std::fs::create_dir_all(&folder).map_err(|source| DialogError::NotWritable { path: folder.clone(), source })?;
+    let result = (|| {
+        let dest = wide::to_wide_os(folder.as_os_str())?;
+        let dest: IShellItem = unsafe { SHCreateItemFromParsingName(PCWSTR(dest.as_ptr()), None)? };
+        let operation: IFileOperation = unsafe { CoCreateInstance(&FileOperation, None, CLSCTX_ALL)? };
+        unsafe {
+            operation.SetOperationFlags(FOF_NO_UI)?;
+            operation.CopyItem(item, &dest, None, None)?;
+            operation.PerformOperations()?;
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_dir_all(&folder);
+        return Err(e);
+    }
+    Ok(folder)
+}
+
+impl<'a> OpenDialog<'a> {
+    /// Open a file, and if it's an archive (currently zip), extract the entry that matches the
+    /// filters. If more than one entry matches, a second dialog is shown inside the archive to
+    /// pick one. Other selections are returned unchanged.
+    pub fn open_or_extract<P>(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>, temp_dir: P)
+        -> Result<Option<OpenSelection>, DialogError> where P: AsRef<Path> {
+        let mut filters = filter.map(|f| f.to_vec()).unwrap_or_default();
+        if !filters.is_empty() {
+            filters.push(FileTypeFilter::new("zip".to_owned(), "Zip archive".to_owned()));
+        }
+        let Some(path) = self.open(Some(&filters).filter(|f| !f.is_empty()).map(|f| f.as_slice()), title)? else {
+            return Ok(None);
+        };
+        if !is_archive(&path) {
+            return Ok(Some(OpenSelection::File(path)));
+        }
+        let archive: IShellItem = unsafe { SHCreateItemFromParsingName(&HSTRING::from(path.as_os_str()), None)? };
+        let mut entries = vec![];
+        list_entries(&archive, "", &mut entries)?;
+        entries.retain(|(name, _)| filter.is_none_or(|f| {
+            let ext = Path::new(name).extension().map(|e| e.to_string_lossy()).unwrap_or_default();
+            f.iter().any(|v| v.matches_extension(&ext))
+        }));
+        let (entry_name, item) = match entries.len() {
+            0 => return Err(DialogError::OpenFailed {
+                path: path.clone(),
+                source: std::io::Error::new(std::io::ErrorKind::NotFound, "the archive has no entries matching the filters")
+            }),
+            1 => entries.pop().unwrap(),
+            _ => match self.pick_entry(&archive, &path, title)? {
+                Some(v) => v,
+                None => return Ok(None)
+            }
+        };
+        let folder = extract(&item, temp_dir.as_ref())?;
+        let file_name = Path::new(&entry_name).file_name().map_or(entry_name.clone().into(), |n| n.to_owned());
+        Ok(Some(OpenSelection::Extracted(ExtractedSelection {
+            original_archive: path,
+            entry_name,
+            extracted_path: folder.join(file_name),
+            folder,
+            keep: false
+        })))
+    }
+
+    fn pick_entry(&self, archive: &IShellItem, path: &Path, title: Option<&str>) -> Result<Option<(String, IShellItem)>, DialogError> {
+        let dialog: IFileOpenDialog = unsafe { CoCreateInstance(&FileOpenDialog, None, CLSCTX_ALL)? };
+        let title = wide::to_wide(title.unwrap_or("Choose a file from the archive"))?;
+        unsafe {
+            dialog.SetTitle(PCWSTR(title.as_ptr()))?;
+            dialog.SetFolder(archive)?;
+        }
+        let owner = self.get_owner_resolution().and_then(|o| o.window());
+        // the user could otherwise browse out of the archive and pick any file
+        let root = events::parsing_name(archive)?;
+        let handler: IFileDialogEvents = DialogEvents::new().restrict_to_tree(root.clone()).into();
+        if !pipeline::show(&dialog.clone().into(), owner, Some(&handler))? {
+            return Ok(None);
+        }
+        let item = unsafe { dialog.GetResult()? };
+        let full = display_name(&item, SIGDN_DESKTOPABSOLUTEPARSING)?;
+        if !events::is_within(&root, &full) || full.len() <= root.trim_end_matches('\\').len() {
+            return Err(DialogError::OpenFailed {
+                path: PathBuf::from(full),
+                source: std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("the selection isn't inside {}", path.display()))
+            });
+        }
+        let name = full[root.trim_end_matches('\\').len()..].trim_start_matches('\\').to_owned();
+        Ok(Some((name, item)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use windows::Win32::System::Com::{ CoInitializeEx, COINIT_APARTMENTTHREADED };
+    use super::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join("riri-file-dialog-archive").join(name);
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn extracted(folder: &Path) -> ExtractedSelection {
+        std::fs::write(folder.join("save.dat"), b"data").unwrap();
+        ExtractedSelection {
+            original_archive: PathBuf::from(r"C:\saves.zip"),
+            entry_name: "save.dat".to_owned(),
+            extracted_path: folder.join("save.dat"),
+            folder: folder.to_owned(),
+            keep: false
+        }
+    }
+
+    #[test]
+    fn archive_extensions() {
+        assert!(is_archive(Path::new(r"C:\saves\backup.zip")));
+        assert!(is_archive(Path::new("BACKUP.ZIP")));
+        assert!(!is_archive(Path::new("backup.zip.txt")));
+        assert!(!is_archive(Path::new("backup.7z")));
+        assert!(!is_archive(Path::new("zip")));
+        assert!(!is_archive(Path::new(r"C:\zip\backup")));
+    }
+
+    #[test]
+    fn extracted_folder_removed_on_drop() {
+        let folder = temp_folder("drop");
+        drop(extracted(&folder));
+        assert!(!folder.exists());
+    }
+
+    #[test]
+    fn persisted_folder_is_kept() {
+        let folder = temp_folder("persist");
+        let path = extracted(&folder).persist();
+        assert_eq!(path, folder.join("save.dat"));
+        assert_eq!(std::fs::read(&path).unwrap(), b"data");
+    }
+
+    #[test]
+    fn entries_are_named_by_their_path() {
+        // a plain folder enumerates the same way as a zip folder
+        let _ = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+        let folder = temp_folder("entries");
+        std::fs::create_dir_all(folder.join(r"saves\old")).unwrap();
+        for file in ["readme.txt", r"saves\slot_01.sav", r"saves\old\slot_00.sav"] {
+            std::fs::write(folder.join(file), b"").unwrap();
+        }
+        let root: IShellItem = unsafe { SHCreateItemFromParsingName(&HSTRING::from(folder.as_os_str()), None) }.unwrap();
+        let mut entries = vec![];
+        list_entries(&root, "", &mut entries).unwrap();
+        let mut names: Vec<String> = entries.into_iter().map(|(name, _)| name).collect();
+        names.sort();
+        assert_eq!(names, ["readme.txt", r"saves\old\slot_00.sav", r"saves\slot_01.sav"]);
+    }
+}
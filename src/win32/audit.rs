@@ -0,0 +1,261 @@
+use std::{
+    collections::VecDeque,
+    fmt::{ Display, Formatter },
+    path::{ Path, PathBuf },
+    time::{ Duration, SystemTime, UNIX_EPOCH }
+};
+use super::{ DialogError, DialogKind, InitialFolderSource, ResolvedConfig };
+
+/// The step a dialog was on when it failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditStage {
+    /// Finding the owner window
+    Owner,
+    /// Applying the configuration to the dialog
+    Configure,
+    /// Showing the dialog and getting the selection
+    Show,
+    /// Checking the selection after the dialog closed, such as for size limits or write access
+    Check
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Selected,
+    Cancelled,
+    Failed {
+        stage: AuditStage,
+        /// The error's message. Paths in it are redacted like the initial folder unless the log
+        /// is verbose.
+        message: String
+    }
+}
+
+impl AuditOutcome {
+    pub(crate) fn failed(stage: AuditStage, error: &DialogError, verbose: bool) -> Self {
+        let message = error.to_string();
+        let message = match (verbose, error_path(error)) {
+            (false, Some(path)) => message.replace(&path.display().to_string(), &redact(path).display().to_string()),
+            _ => message
+        };
+        Self::Failed { stage, message }
+    }
+}
+
+/// One dialog that was shown, as recorded in [`AuditLog`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEntry {
+    pub started: SystemTime,
+    pub duration: Duration,
+    pub kind: DialogKind,
+    /// The folder the dialog opened in. Only the last component is kept unless the log is verbose.
+    pub initial_folder: PathBuf,
+    pub initial_folder_source: InitialFolderSource,
+    /// The filter patterns, such as `*.gmd, *.bin`
+    pub filters: String,
+    pub outcome: AuditOutcome
+}
+
+impl AuditEntry {
+    pub(crate) fn new(config: &ResolvedConfig, started: SystemTime, duration: Duration, outcome: AuditOutcome, verbose: bool) -> Self {
+        Self {
+            started,
+            duration,
+            kind: config.kind,
+            initial_folder: match verbose {
+                true => config.initial_folder.clone(),
+                false => redact(&config.initial_folder)
+            },
+            initial_folder_source: config.initial_folder_source,
+            filters: config.filters.iter().map(|f| f.spec.as_str()).collect::<Vec<_>>().join(", "),
+            outcome
+        }
+    }
+}
+
+impl Display for AuditEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let started = self.started.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        write!(f, "[{}] {:?} dialog in {} ({:?}), filters [{}], {} ms: ", started, self.kind,
+            self.initial_folder.display(), self.initial_folder_source, self.filters, self.duration.as_millis())?;
+        match &self.outcome {
+            AuditOutcome::Selected => write!(f, "selected"),
+            AuditOutcome::Cancelled => write!(f, "cancelled"),
+            AuditOutcome::Failed { stage, message } => write!(f, "failed at {:?}: {}", stage, message)
+        }
+    }
+}
+
+// Keep only the folder's name, which is usually enough to tell where the dialog opened
+fn redact(path: &Path) -> PathBuf {
+    match path.file_name() {
+        Some(name) => Path::new("...").join(name),
+        None => path.to_owned()
+    }
+}
+
+// The path an error's message can contain
+fn error_path(error: &DialogError) -> Option<&Path> {
+    match error {
+        DialogError::NotWritable { path, .. }
+        | DialogError::OpenFailed { path, .. }
+        | DialogError::FileInUse { path, .. }
+        | DialogError::NameMismatch { path, .. }
+        | DialogError::FileTooLarge { path, .. }
+        | DialogError::FolderNotAccessible { path, .. }
+        | DialogError::PatternSelected(path) => Some(path),
+        _ => None
+    }
+}
+
+/// The most recent dialogs shown through a manager, for including in bug reports. Folders and
+/// paths in error messages are redacted to their last component unless the log is set to verbose.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditLog {
+    // oldest first
+    entries: VecDeque<AuditEntry>,
+    limit: usize,
+    verbose: bool
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self { entries: VecDeque::new(), limit: 20, verbose: false }
+    }
+}
+
+impl AuditLog {
+    pub fn get_limit(&self) -> usize { self.limit }
+    /// Set how many entries are kept. Zero turns the log off.
+    pub fn set_limit(&mut self, value: usize) {
+        self.limit = value;
+        while self.entries.len() > value {
+            self.entries.pop_front();
+        }
+    }
+    pub fn get_verbose(&self) -> bool { self.verbose }
+    /// Record full paths. This only affects entries added afterwards.
+    pub fn set_verbose(&mut self, value: bool) { self.verbose = value }
+
+    pub fn get_entries(&self) -> &VecDeque<AuditEntry> { &self.entries }
+    pub fn clear(&mut self) { self.entries.clear() }
+
+    pub(crate) fn record(&mut self, entry: AuditEntry) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.entries.len() == self.limit {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Every entry on its own line, oldest first
+    pub fn format_report(&self) -> String {
+        match self.entries.is_empty() {
+            true => "No dialogs have been shown".to_owned(),
+            false => self.entries.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{ Error as IoError, ErrorKind };
+    use super::*;
+
+    fn entry(seconds: u64, outcome: AuditOutcome) -> AuditEntry {
+        AuditEntry {
+            started: UNIX_EPOCH + Duration::from_secs(seconds),
+            duration: Duration::from_millis(250),
+            kind: DialogKind::Open,
+            initial_folder: PathBuf::from(r"...\models"),
+            initial_folder_source: InitialFolderSource::ManagerDefault,
+            filters: "*.gmd, *.bin".to_owned(),
+            outcome
+        }
+    }
+
+    fn open_failed() -> DialogError {
+        DialogError::OpenFailed {
+            path: PathBuf::from(r"C:\Users\someone\models\player.gmd"),
+            source: IoError::new(ErrorKind::PermissionDenied, "access is denied")
+        }
+    }
+
+    #[test]
+    fn oldest_entries_are_evicted() {
+        let mut log = AuditLog::default();
+        log.set_limit(3);
+        for i in 0..5 {
+            log.record(entry(i, AuditOutcome::Selected));
+        }
+        let started: Vec<u64> = log.get_entries().iter()
+            .map(|e| e.started.duration_since(UNIX_EPOCH).unwrap().as_secs())
+            .collect();
+        assert_eq!(started, [2, 3, 4]);
+        log.set_limit(1);
+        assert_eq!(log.get_entries().len(), 1);
+        assert_eq!(log.get_entries()[0].started, UNIX_EPOCH + Duration::from_secs(4));
+    }
+
+    #[test]
+    fn zero_limit_turns_log_off() {
+        let mut log = AuditLog::default();
+        log.set_limit(0);
+        log.record(entry(1, AuditOutcome::Cancelled));
+        assert!(log.get_entries().is_empty());
+    }
+
+    #[test]
+    fn folders_are_redacted() {
+        assert_eq!(redact(Path::new(r"C:\Users\someone\models")), PathBuf::from(r"...\models"));
+        assert_eq!(redact(Path::new(r"C:\")), PathBuf::from(r"C:\"));
+    }
+
+    #[test]
+    fn error_paths_are_redacted() {
+        let outcome = AuditOutcome::failed(AuditStage::Check, &open_failed(), false);
+        assert_eq!(outcome, AuditOutcome::Failed {
+            stage: AuditStage::Check,
+            message: r"Couldn't open ...\player.gmd: access is denied".to_owned()
+        });
+        let outcome = AuditOutcome::failed(AuditStage::Show, &DialogError::PatternSelected(PathBuf::from(r"C:\Users\someone\*.gmd")), false);
+        let AuditOutcome::Failed { message, .. } = outcome else { panic!() };
+        assert!(!message.contains("someone"), "{}", message);
+    }
+
+    #[test]
+    fn verbose_keeps_error_paths() {
+        let outcome = AuditOutcome::failed(AuditStage::Check, &open_failed(), true);
+        assert_eq!(outcome, AuditOutcome::Failed {
+            stage: AuditStage::Check,
+            message: r"Couldn't open C:\Users\someone\models\player.gmd: access is denied".to_owned()
+        });
+    }
+
+    #[test]
+    fn errors_without_paths_are_unchanged() {
+        let outcome = AuditOutcome::failed(AuditStage::Owner, &DialogError::NoOwnerWindow, false);
+        assert_eq!(outcome, AuditOutcome::Failed {
+            stage: AuditStage::Owner,
+            message: DialogError::NoOwnerWindow.to_string()
+        });
+    }
+
+    #[test]
+    fn report_lines() {
+        let mut log = AuditLog::default();
+        assert_eq!(log.format_report(), "No dialogs have been shown");
+        log.record(entry(10, AuditOutcome::Selected));
+        log.record(entry(20, AuditOutcome::Cancelled));
+        log.record(entry(30, AuditOutcome::failed(AuditStage::Check, &open_failed(), false)));
+        assert_eq!(log.format_report(), [
+            r"[10] Open dialog in ...\models (ManagerDefault), filters [*.gmd, *.bin], 250 ms: selected",
+            r"[20] Open dialog in ...\models (ManagerDefault), filters [*.gmd, *.bin], 250 ms: cancelled",
+            r"[30] Open dialog in ...\models (ManagerDefault), filters [*.gmd, *.bin], 250 ms: failed at Check: Couldn't open ...\player.gmd: access is denied"
+        ].join("\n"));
+        log.clear();
+        assert_eq!(log.format_report(), "No dialogs have been shown");
+    }
+}
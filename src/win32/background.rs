@@ -0,0 +1,417 @@
+use std::{
+    any::Any,
+    panic::{ self, AssertUnwindSafe },
+    path::PathBuf,
+    sync::{
+        Mutex,
+        MutexGuard,
+        PoisonError,
+        atomic::{ AtomicBool, Ordering },
+        mpsc::{ self, Receiver, SendError, Sender, TryRecvError }
+    },
+    os::windows::io::AsRawHandle,
+    thread::{ self, JoinHandle },
+    time::{ Duration, Instant }
+};
+use windows::{
+    core::{ BOOL, Error as WinError },
+    Win32::{
+        Foundation::{ HANDLE, HWND, LPARAM, WPARAM },
+        System::{
+            Com::{ CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED },
+            Threading::GetThreadId
+        },
+        UI::WindowsAndMessaging::{ EnumThreadWindows, IDCANCEL, PostMessageW, WM_COMMAND }
+    }
+};
+use super::{ DialogError, FileDialogManager, FileTypeFilter, OpenDialog, SaveDialog };
+
+/// The result of a dialog shown on a worker thread
+pub type DialogResult = Result<Option<PathBuf>, DialogError>;
+
+// Set while a dialog started with one of the async functions is open
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+// Clears PENDING when dropped, so a panicking dialog or a task that was never run doesn't block
+// later dialogs
+struct PendingGuard;
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        PENDING.store(false, Ordering::Release)
+    }
+}
+
+// Initializes COM for the current thread, and uninitializes it when dropped
+struct ComGuard;
+
+impl ComGuard {
+    fn init() -> Result<Self, WinError> {
+        unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED).ok()? };
+        Ok(Self)
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        unsafe { CoUninitialize() }
+    }
+}
+
+// A dialog to show on the worker thread, given whether COM could be initialized there
+type Task = Box<dyn FnOnce(Result<(), WinError>) + Send>;
+
+// The thread that async dialogs are shown on. It's started by the first async dialog and kept
+// for later ones, so COM is only initialized once.
+struct Worker {
+    sender: Sender<Task>,
+    thread: JoinHandle<()>
+}
+
+static WORKER: Mutex<Option<Worker>> = Mutex::new(None);
+
+impl Worker {
+    fn start() -> Result<Self, DialogError> {
+        let (sender, receiver) = mpsc::channel::<Task>();
+        let thread = thread::Builder::new().name("riri-file-dialog".to_owned()).spawn(move || {
+            let com = ComGuard::init();
+            if let Err(e) = com.as_ref() {
+                log::error!("Couldn't initialize COM on the dialog thread: {}", e);
+            }
+            // tasks catch their own panics, so one failing dialog doesn't stop the thread
+            for task in receiver {
+                task(com.as_ref().map(|_| ()).map_err(Clone::clone));
+            }
+        }).map_err(|e| DialogError::Internal { message: format!("couldn't start the dialog thread: {}", e) })?;
+        Ok(Self { sender, thread })
+    }
+
+    fn lock() -> MutexGuard<'static, Option<Self>> {
+        WORKER.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Send a task, starting the worker if there isn't one or the last one has stopped
+    fn submit(task: Task) -> Result<(), DialogError> {
+        let mut worker = Self::lock();
+        let task = match worker.as_ref().filter(|w| !w.thread.is_finished()) {
+            Some(running) => match running.sender.send(task) {
+                Ok(()) => return Ok(()),
+                Err(SendError(task)) => {
+                    log::warn!("The dialog thread stopped, starting a new one");
+                    task
+                }
+            },
+            None => task
+        };
+        let started = worker.insert(Self::start()?);
+        started.sender.send(task).map_err(|_| DialogError::Internal { message: "the dialog thread stopped immediately".to_owned() })
+    }
+
+    // Stop the worker, cancelling any dialog it has open until it finishes or the timeout passes
+    fn stop(timeout: Duration) -> bool {
+        let mut worker = Self::lock();
+        // it would wait for itself, so it's left running rather than detached
+        if worker.as_ref().is_some_and(|w| w.thread.thread().id() == thread::current().id()) {
+            log::warn!("The dialog thread can't be stopped from one of its own callbacks");
+            return false;
+        }
+        let Some(Worker { sender, thread }) = worker.take() else {
+            return true;
+        };
+        drop(worker);
+        // the thread exits once it's run the tasks it already has
+        drop(sender);
+        let id = unsafe { GetThreadId(HANDLE(thread.as_raw_handle())) };
+        let deadline = Instant::now() + timeout;
+        while !thread.is_finished() {
+            if Instant::now() >= deadline {
+                log::warn!("The dialog thread didn't stop within {:?}, leaving it to exit on its own", timeout);
+                return false;
+            }
+            // EnumThreadWindows reports an error when the thread has no windows
+            let _ = unsafe { EnumThreadWindows(id, Some(cancel_window_proc), LPARAM(0)) };
+            thread::sleep(Duration::from_millis(20));
+        }
+        thread.join().is_ok()
+    }
+}
+
+// Press cancel on a dialog, or on a message box it opened, the same way a user would
+unsafe extern "system" fn cancel_window_proc(window: HWND, _: LPARAM) -> BOOL {
+    let _ = unsafe { PostMessageW(Some(window), WM_COMMAND, WPARAM(IDCANCEL.0 as usize), LPARAM(0)) };
+    true.into()
+}
+
+impl FileDialogManager {
+    /// Remove the global manager and stop the thread that async dialogs are shown on, such as
+    /// before unloading the DLL that created them. A dialog that's still open is cancelled, and
+    /// the thread is given up to `timeout` to finish before it's joined, which uninitializes COM
+    /// on it. Returns false if it didn't finish in time, in which case it's left to exit on its
+    /// own, or if this is called from an async dialog's callback, in which case the thread keeps
+    /// running. Initializing the manager again starts a new thread for the next async dialog.
+    pub fn shutdown(timeout: Duration) -> bool {
+        let stopped = Worker::stop(timeout);
+        *Self::lock() = None;
+        stopped
+    }
+}
+
+// Show a dialog on the worker thread, through an unlocked copy of the manager so the caller can
+// keep using it while the dialog is open. The callback is called with a result even if the
+// dialog panics, and a panicking callback is logged, so the worker keeps running either way.
+fn spawn<J, F>(job: J, callback: F) -> Result<(), DialogError>
+where J: FnOnce(&mut FileDialogManager) -> DialogResult + Send + 'static,
+      F: FnOnce(DialogResult) + Send + 'static {
+    if PENDING.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        return Err(DialogError::AsyncPending);
+    }
+    let guard = PendingGuard;
+    Worker::submit(Box::new(move |com| {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(com, job)))
+            .unwrap_or_else(|payload| Err(DialogError::Internal { message: panic_message(payload.as_ref()) }));
+        // let the callback start another dialog
+        drop(guard);
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| callback(result))) {
+            log::error!("Async dialog callback panicked: {}", panic_message(payload.as_ref()));
+        }
+    }))
+}
+
+fn run<J>(com: Result<(), WinError>, job: J) -> DialogResult where J: FnOnce(&mut FileDialogManager) -> DialogResult {
+    com?;
+    FileDialogManager::with_unlocked(job).unwrap_or_else(|e| Err(e.into()))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => (*s).to_owned(),
+        None => payload.downcast_ref::<String>().cloned().unwrap_or_else(|| "the dialog panicked".to_owned())
+    }
+}
+
+impl OpenDialog<'_> {
+    /// Show an open dialog on a worker thread and call `callback` there with the result, without
+    /// blocking the calling thread. The dialog is created from the manager's settings, parented to
+    /// its owner. Only one dialog can be shown this way at a time, and starting another while
+    /// one is open fails with [`DialogError::AsyncPending`].
+    pub fn open_async<F>(filter: Option<&[FileTypeFilter]>, title: Option<&str>, callback: F) -> Result<(), DialogError>
+    where F: FnOnce(DialogResult) + Send + 'static {
+        let filter = filter.map(<[FileTypeFilter]>::to_vec);
+        let title = title.map(str::to_owned);
+        spawn(move |mgr| OpenDialog::create(mgr)?.open(filter.as_deref(), title.as_deref()), callback)
+    }
+
+    /// Folder picking version of [`OpenDialog::open_async`]
+    pub fn open_folder_async<F>(title: Option<&str>, callback: F) -> Result<(), DialogError>
+    where F: FnOnce(DialogResult) + Send + 'static {
+        let title = title.map(str::to_owned);
+        spawn(move |mgr| OpenDialog::create(mgr)?.open_folder(title.as_deref()), callback)
+    }
+}
+
+impl SaveDialog<'_> {
+    /// Save version of [`OpenDialog::open_async`]
+    pub fn save_async<F>(filter: Option<&[FileTypeFilter]>, title: Option<&str>, callback: F) -> Result<(), DialogError>
+    where F: FnOnce(DialogResult) + Send + 'static {
+        let filter = filter.map(<[FileTypeFilter]>::to_vec);
+        let title = title.map(str::to_owned);
+        spawn(move |mgr| SaveDialog::create(mgr)?.save(filter.as_deref(), title.as_deref()), callback)
+    }
+}
+
+/// What [`DialogHandle::try_result`] found
+#[derive(Debug)]
+pub enum DialogStatus {
+    /// The dialog is still open
+    Open,
+    /// The dialog closed with this result
+    Closed(DialogResult),
+    /// There's nothing more to receive, because the result was already returned or the worker
+    /// thread stopped without sending one
+    Stopped
+}
+
+/// A dialog shown on a worker thread, for hosts that would rather check for the result each
+/// frame than receive a callback. See [`OpenDialog::open_async`].
+#[derive(Debug)]
+pub struct DialogHandle {
+    receiver: Receiver<DialogResult>
+}
+
+impl DialogHandle {
+    pub fn open(filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Self, DialogError> {
+        let (sender, receiver) = mpsc::channel();
+        OpenDialog::open_async(filter, title, move |r| { let _ = sender.send(r); })?;
+        Ok(Self { receiver })
+    }
+
+    pub fn open_folder(title: Option<&str>) -> Result<Self, DialogError> {
+        let (sender, receiver) = mpsc::channel();
+        OpenDialog::open_folder_async(title, move |r| { let _ = sender.send(r); })?;
+        Ok(Self { receiver })
+    }
+
+    pub fn save(filter: Option<&[FileTypeFilter]>, title: Option<&str>) -> Result<Self, DialogError> {
+        let (sender, receiver) = mpsc::channel();
+        SaveDialog::save_async(filter, title, move |r| { let _ = sender.send(r); })?;
+        Ok(Self { receiver })
+    }
+
+    /// Check whether the dialog has closed without waiting for it. The result is returned once
+    /// as [`DialogStatus::Closed`], and every later call returns [`DialogStatus::Stopped`].
+    pub fn try_result(&self) -> DialogStatus {
+        match self.receiver.try_recv() {
+            Ok(result) => DialogStatus::Closed(result),
+            Err(TryRecvError::Empty) => DialogStatus::Open,
+            Err(TryRecvError::Disconnected) => DialogStatus::Stopped
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{ ManagerError, tests::GLOBAL };
+    use super::*;
+
+    // Run a job the way the async functions do, without showing a dialog
+    fn spawn_and_wait<J>(job: J) -> DialogResult
+    where J: FnOnce(&mut FileDialogManager) -> DialogResult + Send + 'static {
+        let (sender, receiver) = mpsc::channel();
+        spawn(job, move |r| { let _ = sender.send(r); }).unwrap();
+        receiver.recv_timeout(Duration::from_secs(5)).expect("the callback wasn't called")
+    }
+
+    #[test]
+    fn panic_is_reported_to_the_callback() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        let result = spawn_and_wait(|_| panic!("dialog exploded"));
+        assert!(matches!(result, Err(DialogError::Internal { message }) if message == "dialog exploded"));
+        // the panic doesn't leave a dialog marked as open
+        let folder = std::env::temp_dir().join("after-panic");
+        let expected = folder.clone();
+        assert_eq!(spawn_and_wait(move |_| Ok(Some(folder))).unwrap(), Some(expected));
+        *FileDialogManager::lock() = None;
+    }
+
+    #[test]
+    fn worker_survives_panics() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        // each job reports the thread it ran on
+        let (ids, seen) = mpsc::channel();
+        let send = ids.clone();
+        let result = spawn_and_wait(move |_| {
+            let _ = send.send(thread::current().id());
+            panic!("selection hook panicked")
+        });
+        assert!(matches!(result, Err(DialogError::Internal { message }) if message == "selection hook panicked"));
+
+        let (sender, receiver) = mpsc::channel();
+        let send = ids.clone();
+        spawn(move |_| {
+            let _ = send.send(thread::current().id());
+            Ok(None)
+        }, move |r| {
+            let _ = sender.send(r.is_ok());
+            panic!("callback panicked")
+        }).unwrap();
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+
+        let send = ids;
+        let result = spawn_and_wait(move |_| {
+            let _ = send.send(thread::current().id());
+            Ok(None)
+        });
+        assert_eq!(result.unwrap(), None);
+        let ids: Vec<_> = seen.try_iter().collect();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().all(|id| *id == ids[0]), "{:?}", ids);
+        assert_ne!(ids[0], thread::current().id());
+        *FileDialogManager::lock() = None;
+    }
+
+    // The thread that jobs run on
+    fn worker_thread() -> thread::ThreadId {
+        let (sender, receiver) = mpsc::channel();
+        spawn_and_wait(move |_| {
+            let _ = sender.send(thread::current().id());
+            Ok(None)
+        }).unwrap();
+        receiver.recv().unwrap()
+    }
+
+    #[test]
+    fn shutdown_and_initialize_again() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        let first = worker_thread();
+        assert!(FileDialogManager::shutdown(Duration::from_secs(5)));
+        assert!(FileDialogManager::try_get().is_none());
+        assert!(Worker::lock().is_none());
+        // nothing to stop the second time
+        assert!(FileDialogManager::shutdown(Duration::from_secs(5)));
+
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        let second = worker_thread();
+        assert_ne!(first, second);
+        assert!(FileDialogManager::shutdown(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn shutdown_from_callback_keeps_worker() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        let first = worker_thread();
+        let (sender, receiver) = mpsc::channel();
+        spawn(|_| Ok(None), move |_| {
+            let _ = sender.send(FileDialogManager::shutdown(Duration::from_secs(1)));
+        }).unwrap();
+        assert!(!receiver.recv_timeout(Duration::from_secs(5)).unwrap());
+        // the same worker is still registered and handles the next dialog
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        assert_eq!(worker_thread(), first);
+        assert!(FileDialogManager::shutdown(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn shutdown_waits_for_open_dialog() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        let (sender, receiver) = mpsc::channel();
+        spawn(|_| {
+            // stands in for a dialog that takes a moment to close
+            thread::sleep(Duration::from_millis(200));
+            Ok(None)
+        }, move |r| { let _ = sender.send(r); }).unwrap();
+        assert!(FileDialogManager::shutdown(Duration::from_secs(5)));
+        // the dialog finished and its callback ran before the thread was joined
+        assert_eq!(receiver.try_recv().unwrap().unwrap(), None);
+    }
+
+    #[test]
+    fn shutdown_gives_up_after_timeout() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir());
+        let (release, blocked) = mpsc::channel::<()>();
+        let (sender, receiver) = mpsc::channel();
+        spawn(move |_| {
+            // doesn't have a window, so it can't be cancelled
+            let _ = blocked.recv();
+            Ok(None)
+        }, move |r| { let _ = sender.send(r); }).unwrap();
+        assert!(!FileDialogManager::shutdown(Duration::from_millis(100)));
+        assert!(FileDialogManager::try_get().is_none());
+        // the thread still finishes once the dialog does
+        release.send(()).unwrap();
+        assert!(receiver.recv_timeout(Duration::from_secs(5)).unwrap().is_ok());
+    }
+
+    #[test]
+    fn missing_manager_is_reported_to_the_callback() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        *FileDialogManager::lock() = None;
+        let result = spawn_and_wait(|_| Ok(None));
+        assert!(matches!(result, Err(DialogError::Manager(ManagerError::Uninitialized))));
+    }
+}
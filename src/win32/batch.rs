@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+use crate::options::{ DialogFlags, DialogOptions };
+use super::{
+    DialogError,
+    FileDialog,
+    FileTypeFilter,
+    OptionLayer,
+    SaveDialog,
+    confirm::unique_path,
+    events::DialogEvents,
+    pipeline::DialogPipeline
+};
+
+/// Numbered paths for exporting several files from one save dialog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchPaths {
+    pub folder: PathBuf,
+    /// The base name after removing characters that aren't allowed in file names
+    pub base: String,
+    /// `base_000.ext`, `base_001.ext`... in order, none of which existed when they were created
+    pub paths: Vec<PathBuf>,
+    /// Indices into `paths` that were renamed because a file with the numbered name already existed
+    pub renamed: Vec<usize>
+}
+
+impl BatchPaths {
+    /// Generate `count` numbered paths in a folder. Numbers are padded to at least three digits.
+    pub fn generate(folder: PathBuf, base: &str, extension: &str, count: usize) -> Self {
+        let base = Self::sanitize(base);
+        let extension = FileTypeFilter::normalize_extension(extension);
+        let width = count.saturating_sub(1).to_string().len().max(3);
+        let mut renamed = vec![];
+        let paths = (0..count).map(|i| {
+            let path = folder.join(format!("{}_{:0width$}.{}", base, i, extension, width = width));
+            match path.exists() {
+                true => {
+                    renamed.push(i);
+                    unique_path(&path)
+                },
+                false => path
+            }
+        }).collect();
+        Self { folder, base, paths, renamed }
+    }
+
+    /// Remove characters that Windows doesn't allow in file names, and trailing dots and spaces.
+    /// Reserved device names such as `CON` or `com1.tar` get a leading underscore.
+    pub fn sanitize(base: &str) -> String {
+        let out: String = base.chars()
+            .filter(|c| !matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*') && !c.is_control())
+            .collect();
+        match out.trim_end_matches(['.', ' ']) {
+            "" => "export".to_owned(),
+            v if Self::is_reserved(v) => format!("_{}", v),
+            v => v.to_owned()
+        }
+    }
+
+    // Windows ignores everything after the first dot, and trailing spaces, when checking for
+    // device names
+    fn is_reserved(name: &str) -> bool {
+        let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ').to_ascii_uppercase();
+        match stem.as_str() {
+            "CON" | "PRN" | "AUX" | "NUL" => true,
+            v => (v.starts_with("COM") || v.starts_with("LPT"))
+                && matches!(v.as_bytes().get(3..), Some([b'1'..=b'9']))
+        }
+    }
+}
+
+impl<'a> SaveDialog<'a> {
+    /// Ask for a folder and base name once, then generate `count` numbered paths with the given
+    /// extension. The name entered in the dialog is used as the base name, and no file is created
+    /// for it.
+    pub fn save_batch(&mut self, count: usize, extension: &str, default_base: &str, title: Option<&str>) -> Result<Option<BatchPaths>, DialogError> {
+        let extension = FileTypeFilter::normalize_extension(extension).to_owned();
+        let filter = [FileTypeFilter::new(extension.clone(), format!("{} files", extension.to_uppercase()))];
+        let mut config = self.resolve(Some(&filter), title);
+        config.suggested_name = Some(default_base.to_owned());
+        config.default_extension = Some(extension.clone());
+        config.add_layer(OptionLayer::Preset, &DialogOptions::new().skip_test_create(true).flag(DialogFlags::OVERWRITE_PROMPT, false));
+        config.confirm_overwrite = false;
+        // the base path is never created, so it's only remembered if its folder exists
+        config.remember_selection = false;
+        let Some(selected) = self.run(&config, DialogEvents::new())? else {
+            return Ok(None);
+        };
+        let folder = selected.parent().map_or_else(|| self.get_default_path().to_owned(), |p| p.to_owned());
+        if folder.is_dir() {
+            self.remember(config.kind, &selected);
+        }
+        let base = selected.file_stem().map_or(default_base.into(), |v| v.to_string_lossy());
+        let out = BatchPaths::generate(folder, &base, &extension, count);
+        if !out.renamed.is_empty() {
+            log::warn!("{} of {} batch paths already existed and were renamed", out.renamed.len(), count);
+        }
+        Ok(Some(out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_removes_invalid_characters() {
+        assert_eq!(BatchPaths::sanitize("a<b>c:d\"e/f\\g|h?i*j"), "abcdefghij");
+        assert_eq!(BatchPaths::sanitize("name. . "), "name");
+        assert_eq!(BatchPaths::sanitize(" ..?"), "export");
+    }
+
+    #[test]
+    fn sanitize_renames_device_names() {
+        assert_eq!(BatchPaths::sanitize("CON"), "_CON");
+        assert_eq!(BatchPaths::sanitize("nul"), "_nul");
+        assert_eq!(BatchPaths::sanitize("Com1"), "_Com1");
+        assert_eq!(BatchPaths::sanitize("LPT9.tar"), "_LPT9.tar");
+        assert_eq!(BatchPaths::sanitize("aux .txt"), "_aux .txt");
+    }
+
+    #[test]
+    fn sanitize_keeps_similar_names() {
+        assert_eq!(BatchPaths::sanitize("CONSOLE"), "CONSOLE");
+        assert_eq!(BatchPaths::sanitize("COM0"), "COM0");
+        assert_eq!(BatchPaths::sanitize("COM10"), "COM10");
+        assert_eq!(BatchPaths::sanitize("my_nul"), "my_nul");
+    }
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join("riri-file-dialog-batch").join(name);
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn names(batch: &BatchPaths) -> Vec<String> {
+        batch.paths.iter().map(|p| p.file_name().unwrap().to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn generate_numbers_paths() {
+        let folder = temp_folder("numbers");
+        let batch = BatchPaths::generate(folder.clone(), "frame", ".PNG", 3);
+        assert_eq!(names(&batch), ["frame_000.PNG", "frame_001.PNG", "frame_002.PNG"]);
+        assert!(batch.paths.iter().all(|p| p.parent() == Some(folder.as_path())));
+        assert!(batch.renamed.is_empty());
+        assert_eq!(batch.base, "frame");
+    }
+
+    #[test]
+    fn generate_widens_numbers() {
+        let batch = BatchPaths::generate(temp_folder("width"), "frame", "png", 1001);
+        assert_eq!(batch.paths.len(), 1001);
+        assert_eq!(names(&batch)[0], "frame_0000.png");
+        assert_eq!(names(&batch)[1000], "frame_1000.png");
+        assert!(BatchPaths::generate(temp_folder("empty"), "frame", "png", 0).paths.is_empty());
+    }
+
+    #[test]
+    fn generate_renames_existing_files() {
+        let folder = temp_folder("existing");
+        for name in ["frame_000.png", "frame_000 (2).png", "frame_002.png"] {
+            std::fs::write(folder.join(name), "").unwrap();
+        }
+        let batch = BatchPaths::generate(folder, "frame", "png", 4);
+        assert_eq!(names(&batch), ["frame_000 (3).png", "frame_001.png", "frame_002 (2).png", "frame_003.png"]);
+        assert_eq!(batch.renamed, vec![0, 2]);
+        assert!(batch.paths.iter().all(|p| !p.exists()));
+    }
+
+    #[test]
+    fn generated_paths_are_distinct() {
+        let folder = temp_folder("distinct");
+        // every numbered name and its first renamed form already exist
+        for i in 0..5 {
+            std::fs::write(folder.join(format!("frame_{:03}.png", i)), "").unwrap();
+            std::fs::write(folder.join(format!("frame_{:03} (2).png", i)), "").unwrap();
+        }
+        let batch = BatchPaths::generate(folder, "frame", "png", 5);
+        let unique: std::collections::HashSet<_> = batch.paths.iter().collect();
+        assert_eq!(unique.len(), 5);
+        assert_eq!(batch.renamed, vec![0, 1, 2, 3, 4]);
+        assert!(batch.paths.iter().all(|p| !p.exists()));
+    }
+
+    #[test]
+    fn generate_avoids_device_names() {
+        let folder = temp_folder("device");
+        let batch = BatchPaths::generate(folder.clone(), "con", "txt", 2);
+        assert_eq!(batch.base, "_con");
+        assert_eq!(names(&batch), ["_con_000.txt", "_con_001.txt"]);
+        // the files can actually be created
+        for path in &batch.paths {
+            std::fs::write(path, "").unwrap();
+        }
+        let batch = BatchPaths::generate(folder, "LPT1.bak", "txt", 1);
+        assert_eq!(names(&batch), ["_LPT1.bak_000.txt"]);
+    }
+}
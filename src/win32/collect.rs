@@ -0,0 +1,158 @@
+use std::{
+    os::windows::ffi::OsStrExt,
+    path::{ Path, PathBuf }
+};
+use windows::{
+    core::HSTRING,
+    Win32::UI::Shell::{ IShellItem, SHCreateItemFromParsingName }
+};
+use super::{
+    DialogError,
+    FileTypeFilter,
+    OpenDialog,
+    events::DialogEvents,
+    pipeline::DialogPipeline
+};
+
+impl<'a> OpenDialog<'a> {
+    /// Keep showing the dialog so the user can add files one at a time, until they cancel. The same
+    /// dialog is reused, and each time it opens in the folder of the last selection. Files that
+    /// were already picked aren't added again. `on_added` is called with every file picked so far
+    /// after each new one, so the host can show progress; pass `|_| ()` if that isn't needed.
+    ///
+    /// Cancelling ends the collection normally. If an error happens after some files were picked,
+    /// the collection ends there and those files are returned, so the error is only returned
+    /// when it happens before the first pick.
+    pub fn collect_until_cancel<F>(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>, on_added: F)
+        -> Result<Vec<PathBuf>, DialogError> where F: FnMut(&[PathBuf]) {
+        let config = self.resolve(filter, title);
+        let first = self.run(&config, DialogEvents::new())?;
+        // the configuration is already applied, so later passes only move to the folder and show
+        Ok(collect(first, |path| self.open_in_folder_of(path).and_then(|_| self.run_again(&config, DialogEvents::new())), on_added))
+    }
+
+    fn open_in_folder_of(&self, path: &Path) -> Result<(), DialogError> {
+        if let Some(folder) = path.parent() {
+            let item: IShellItem = unsafe { SHCreateItemFromParsingName(&HSTRING::from(folder.as_os_str()), None)? };
+            unsafe { self.handle.SetFolder(&item)? };
+        }
+        Ok(())
+    }
+}
+
+// Windows compares names ignoring case. Units that aren't valid UTF-16 are kept as they are, so
+// two different unpaired surrogates don't compare equal.
+fn fold_case(path: &Path) -> Vec<Result<char, u16>> {
+    char::decode_utf16(path.as_os_str().encode_wide())
+        .flat_map(|c| match c {
+            Ok(c) => c.to_lowercase().map(Ok).collect::<Vec<_>>(),
+            Err(e) => vec![Err(e.unpaired_surrogate())]
+        })
+        .collect()
+}
+
+/// Add `first` and then whatever `show_next` returns for the last pick, until it returns `None`.
+/// Errors from `show_next` end the collection with the files picked so far.
+fn collect<N, F>(first: Option<PathBuf>, mut show_next: N, mut on_added: F) -> Vec<PathBuf>
+    where N: FnMut(&Path) -> Result<Option<PathBuf>, DialogError>, F: FnMut(&[PathBuf]) {
+    let mut out: Vec<PathBuf> = vec![];
+    let mut seen = vec![];
+    let mut next = first;
+    while let Some(path) = next {
+        let key = fold_case(&path);
+        if seen.contains(&key) {
+            log::debug!("{} was already picked", path.display());
+        } else {
+            seen.push(key);
+            out.push(path.clone());
+            on_added(&out);
+        }
+        next = match show_next(&path) {
+            Ok(v) => v,
+            Err(e) => {
+                log::warn!("Stopped collecting files after {} were picked: {}", out.len(), e);
+                break;
+            }
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ collections::VecDeque, ffi::OsString, os::windows::ffi::OsStringExt };
+    use super::*;
+
+    fn same(a: &Path, b: &Path) -> bool {
+        fold_case(a) == fold_case(b)
+    }
+
+    fn wide_path(units: &[u16]) -> PathBuf {
+        PathBuf::from(OsString::from_wide(units))
+    }
+
+    // Replay `picks` after the first one, recording which paths the next pass was opened from
+    fn replay(picks: Vec<Result<Option<PathBuf>, DialogError>>) -> (Vec<PathBuf>, Vec<PathBuf>, Vec<usize>) {
+        let mut picks = VecDeque::from(picks);
+        let first = picks.pop_front().unwrap().unwrap();
+        let (mut opened_from, mut progress) = (vec![], vec![]);
+        let out = collect(first, |path| {
+            opened_from.push(path.to_owned());
+            picks.pop_front().unwrap()
+        }, |added| progress.push(added.len()));
+        assert!(picks.is_empty());
+        (out, opened_from, progress)
+    }
+
+    fn pick(path: &str) -> Result<Option<PathBuf>, DialogError> {
+        Ok(Some(PathBuf::from(path)))
+    }
+
+    #[test]
+    fn case_is_ignored() {
+        assert!(same(Path::new(r"C:\Saves\SLOT.sav"), Path::new(r"c:\saves\slot.SAV")));
+        assert!(same(Path::new(r"C:\Ärger.txt"), Path::new(r"C:\ärger.txt")));
+        assert!(!same(Path::new(r"C:\saves\slot1.sav"), Path::new(r"C:\saves\slot2.sav")));
+    }
+
+    #[test]
+    fn unpaired_surrogates_are_compared_exactly() {
+        // both of these would be U+FFFD after a lossy conversion
+        let a = wide_path(&[0x43, 0x3A, 0x5C, 0xD800]);
+        let b = wide_path(&[0x43, 0x3A, 0x5C, 0xD801]);
+        assert!(!same(&a, &b));
+        assert!(same(&a, &wide_path(&[0x63, 0x3A, 0x5C, 0xD800])));
+    }
+
+    #[test]
+    fn repeated_picks_are_skipped() {
+        let (out, opened_from, progress) = replay(vec![
+            pick(r"C:\saves\a.sav"),
+            pick(r"C:\saves\b.sav"),
+            pick(r"C:\SAVES\A.SAV"),
+            pick(r"C:\saves\c.sav"),
+            Ok(None)
+        ]);
+        assert_eq!(out, [r"C:\saves\a.sav", r"C:\saves\b.sav", r"C:\saves\c.sav"].map(PathBuf::from));
+        // each pass opens from the last pick, even one that was skipped
+        assert_eq!(opened_from, [r"C:\saves\a.sav", r"C:\saves\b.sav", r"C:\SAVES\A.SAV", r"C:\saves\c.sav"].map(PathBuf::from));
+        assert_eq!(progress, [1, 2, 3]);
+    }
+
+    #[test]
+    fn cancelling_first_pick_is_empty() {
+        let (out, opened_from, progress) = replay(vec![Ok(None)]);
+        assert!(out.is_empty() && opened_from.is_empty() && progress.is_empty());
+    }
+
+    #[test]
+    fn error_after_picks_keeps_them() {
+        let (out, _, progress) = replay(vec![
+            pick(r"C:\saves\a.sav"),
+            pick(r"C:\saves\b.sav"),
+            Err(DialogError::Internal { message: "the dialog went away".to_owned() })
+        ]);
+        assert_eq!(out, [r"C:\saves\a.sav", r"C:\saves\b.sav"].map(PathBuf::from));
+        assert_eq!(progress, [1, 2]);
+    }
+}
@@ -0,0 +1,688 @@
+use std::{
+    fmt::{ Display, Formatter },
+    path::{ Path, PathBuf, MAIN_SEPARATOR }
+};
+use windows::{
+    core::{ GUID, Interface, PCWSTR },
+    Win32::{
+        Foundation::HWND,
+        UI::Shell::{
+            Common::COMDLG_FILTERSPEC,
+            FDAP_BOTTOM,
+            FILEOPENDIALOGOPTIONS,
+            IFileDialog,
+            IFileSaveDialog,
+            IShellItem,
+            SHCreateItemFromParsingName
+        }
+    }
+};
+use crate::{ options::{ DialogFlags, DialogOptions }, utils::wide };
+use super::{ DialogError, FileDialogUtils, FileTypeFilter, FileTypeFilterWin32, NamePattern, PathStyle };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DialogKind {
+    Open,
+    OpenFolder,
+    Save,
+    /// A folder picker that only allows selecting drive roots
+    Drive
+}
+
+/// Where the folder a dialog starts in came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitialFolderSource {
+    /// The manager's remembered open/save folder
+    ManagerDefault,
+    /// The parent folder of a path passed to [`SaveDialog::save_as`](super::SaveDialog::save_as)
+    SuggestedPath,
+    /// An environment variable that overrides the manager's default, see [`DEFAULT_DIR_VARIABLE`]
+    Environment(&'static str)
+}
+
+/// Environment variable that makes every dialog start in a folder, overriding the manager's
+/// remembered default. `_OPEN` and `_SAVE` suffixed versions only apply to one kind of dialog and
+/// take precedence over this one. Folders chosen for a single call still win.
+pub const DEFAULT_DIR_VARIABLE: &str = "RIRI_FILE_DIALOG_DEFAULT_DIR";
+
+impl InitialFolderSource {
+    /// Get the folder from the first of the default folder environment variables that's set for
+    /// this kind of dialog and names an existing folder
+    pub(crate) fn from_environment(kind: DialogKind) -> Option<(PathBuf, Self)> {
+        const OPEN: &str = "RIRI_FILE_DIALOG_DEFAULT_DIR_OPEN";
+        const SAVE: &str = "RIRI_FILE_DIALOG_DEFAULT_DIR_SAVE";
+        let specific = match kind {
+            DialogKind::Save => SAVE,
+            _ => OPEN
+        };
+        [specific, DEFAULT_DIR_VARIABLE].into_iter().find_map(|name| {
+            let value = std::env::var_os(name).filter(|v| !v.is_empty())?;
+            let folder = PathBuf::from(value);
+            match folder.is_dir() {
+                true => {
+                    log::info!("Starting dialog in {} from {}", folder.display(), name);
+                    Some((folder, Self::Environment(name)))
+                },
+                false => {
+                    log::warn!("Ignoring {}, {} isn't a folder", name, folder.display());
+                    None
+                }
+            }
+        })
+    }
+}
+
+/// Settings given to one dialog through its builder methods, applied on top of the manager's
+/// defaults when the dialog's configuration is resolved
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CallSettings {
+    pub(crate) owner: Option<HWND>,
+    pub(crate) context: Option<String>,
+    pub(crate) options: DialogOptions,
+    pub(crate) name_pattern: Option<(String, PatternPolicy)>,
+    pub(crate) verify_writable: bool,
+    pub(crate) collect_metadata: bool,
+    pub(crate) minimal_chrome: bool,
+    pub(crate) places: Vec<PathBuf>,
+    pub(crate) max_file_size: Option<u64>,
+    pub(crate) name_rule: Option<(NamePattern, String)>,
+    pub(crate) path_style: PathStyle,
+    pub(crate) filter_index: Option<usize>,
+    pub(crate) folder_access: Option<FolderAccess>
+}
+
+impl CallSettings {
+    pub(crate) fn apply(&self, config: &mut ResolvedConfig) {
+        config.add_options(&self.options);
+        if self.name_pattern.is_some() {
+            config.name_pattern = self.name_pattern.clone();
+        }
+        config.verify_writable |= self.verify_writable;
+        if self.max_file_size.is_some() {
+            config.max_file_size = self.max_file_size;
+        }
+        if self.name_rule.is_some() {
+            config.name_rule = self.name_rule.clone();
+        }
+        if self.path_style != PathStyle::Native {
+            config.path_style = self.path_style;
+        }
+        if self.folder_access.is_some() {
+            config.folder_access = self.folder_access;
+        }
+        if self.filter_index.is_some() {
+            config.filter_index = self.filter_index;
+        }
+        config.collect_metadata |= self.collect_metadata;
+        if self.minimal_chrome {
+            config.places.clear();
+        }
+        // Places added for this dialog go above the ones from history
+        config.places.splice(0..0, self.places.iter().cloned());
+    }
+}
+
+/// Where a change to a dialog's options came from. Later layers take precedence over earlier ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionLayer {
+    /// The manager's baseline, see [`FileDialogManager::set_default_options`](super::FileDialogManager::set_default_options)
+    Manager,
+    /// The crate's defaults for the kind of dialog
+    Preset,
+    /// Options given for one dialog
+    Call
+}
+
+impl Display for OptionLayer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Manager => "manager",
+            Self::Preset => "preset",
+            Self::Call => "call"
+        })
+    }
+}
+
+/// What an open dialog does when the user accepts a wildcard name pattern rather than a file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PatternPolicy {
+    /// Show the dialog again with the pattern
+    #[default]
+    Reprompt,
+    /// Return [`DialogError::PatternSelected`]
+    Error
+}
+
+/// What a selected folder must allow, see [`OpenDialog::require_folder_access`](super::OpenDialog::require_folder_access)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FolderAccess {
+    /// The folder's contents can be listed
+    Read,
+    /// The folder's contents can be listed and new files can be created in it
+    ReadWrite
+}
+
+/// Settings for [`SaveDialog::save_with_options`](super::SaveDialog::save_with_options)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveOptions {
+    pub title: Option<String>,
+    /// Name put in the file name box
+    pub file_name: Option<String>,
+    /// Extension added to names typed without one. A leading `.` is removed.
+    pub default_extension: Option<String>,
+    /// Existing file to start with selected, in its folder. This replaces `file_name`.
+    pub initial_item: Option<PathBuf>,
+    /// Ask before overwriting an existing file. This is on by default, and uses
+    /// [`confirm_overwrite`](super::confirm_overwrite) instead of the shell's prompt when the
+    /// manager is set up to.
+    pub prompt_overwrite: bool
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self { title: None, file_name: None, default_extension: None, initial_item: None, prompt_overwrite: true }
+    }
+}
+
+impl SaveOptions {
+    pub fn new() -> Self { Self::default() }
+    pub fn title<S>(mut self, value: S) -> Self where S: Into<String> { self.title = Some(value.into()); self }
+    pub fn file_name<S>(mut self, value: S) -> Self where S: Into<String> { self.file_name = Some(value.into()); self }
+    pub fn default_extension<S>(mut self, value: S) -> Self where S: Into<String> { self.default_extension = Some(value.into()); self }
+    pub fn initial_item<P>(mut self, value: P) -> Self where P: Into<PathBuf> { self.initial_item = Some(value.into()); self }
+    pub fn prompt_overwrite(mut self, value: bool) -> Self { self.prompt_overwrite = value; self }
+
+    pub(crate) fn apply(&self, config: &mut ResolvedConfig) {
+        if let Some(name) = self.file_name.as_ref() {
+            config.suggested_name = Some(name.clone());
+        }
+        if let Some(ext) = self.default_extension.as_ref() {
+            config.default_extension = Some(FileTypeFilter::normalize_extension(ext).to_owned());
+        }
+        if let Some(item) = self.initial_item.as_ref() {
+            config.save_as_item = Some(item.clone());
+        }
+        if !self.prompt_overwrite {
+            config.confirm_overwrite = false;
+            config.add_options(&DialogOptions::new().flag(DialogFlags::OVERWRITE_PROMPT, false));
+        }
+    }
+}
+
+/// A full path split into the parts a save dialog is configured with. Any part that the path
+/// doesn't have is left as `None` so the dialog's usual default is used for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SuggestedPath {
+    pub folder: Option<PathBuf>,
+    pub file_name: Option<String>,
+    /// Extension without the leading dot
+    pub extension: Option<String>,
+    /// Caller's index of the first filter that matches the extension
+    pub filter_index: Option<usize>
+}
+
+impl SuggestedPath {
+    pub fn decompose(path: &Path, filters: &[FileTypeFilter]) -> Self {
+        // "C:\\exports\\" names a folder, but Path::file_name would return "exports"
+        let text = path.as_os_str().to_string_lossy();
+        let is_folder = text.ends_with(MAIN_SEPARATOR) || text.ends_with('/') || path.file_name().is_none();
+        if is_folder {
+            let folder = (!text.is_empty()).then(|| path.to_owned());
+            return Self { folder, ..Default::default() };
+        }
+        let folder = path.parent().filter(|p| !p.as_os_str().is_empty()).map(Path::to_owned);
+        let file_name = path.file_name().map(|v| v.to_string_lossy().into_owned());
+        let extension = path.extension().map(|v| v.to_string_lossy().into_owned());
+        let filter_index = extension.as_deref()
+            .and_then(|ext| filters.iter().position(|f| f.matches_extension(ext)));
+        Self { folder, file_name, extension, filter_index }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedFilter {
+    /// Zero-based index into the filters passed by the caller. Once duplicates are removed this
+    /// can differ from the filter's position in the dialog's dropdown.
+    pub index: usize,
+    pub description: String,
+    pub spec: String
+}
+
+/// Everything a dialog will be configured with before it's shown, after combining the manager's
+/// defaults with the arguments for that call. Dialogs apply exactly this, so it can be used to
+/// check what a dialog would look like without showing one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedConfig {
+    pub kind: DialogKind,
+    pub title: String,
+    pub initial_folder: PathBuf,
+    pub initial_folder_source: InitialFolderSource,
+    pub filters: Vec<ResolvedFilter>,
+    /// Indices of the caller's filters that were dropped as duplicates of an earlier filter
+    pub removed_filters: Vec<usize>,
+    /// Flags added on top of the dialog's default options
+    pub options: FILEOPENDIALOGOPTIONS,
+    /// Flags removed from the dialog's default options
+    pub options_cleared: FILEOPENDIALOGOPTIONS,
+    /// The changes that `options` and `options_cleared` were built from, in the order they were applied
+    pub option_layers: Vec<(OptionLayer, DialogOptions)>,
+    /// Ask with [`confirm_overwrite`](super::confirm_overwrite) when the selected file exists
+    pub confirm_overwrite: bool,
+    pub suggested_name: Option<String>,
+    /// Existing file that a save dialog starts with selected, replacing `suggested_name` and the
+    /// initial folder
+    pub save_as_item: Option<PathBuf>,
+    /// Extension appended to typed names that don't have one, without the leading dot
+    pub default_extension: Option<String>,
+    /// Caller's index of the filter that's selected when the dialog opens
+    pub filter_index: Option<usize>,
+    /// Wildcard pattern put in the file name box to narrow the listed files. Can't be combined
+    /// with `suggested_name`.
+    pub name_pattern: Option<(String, PatternPolicy)>,
+    /// Check that the selection can be written to before returning it
+    pub verify_writable: bool,
+    /// Collect a [`SelectionInfo`](super::SelectionInfo) for the selection
+    pub collect_metadata: bool,
+    /// Remember the selection as the new default and in the folder history
+    pub remember_selection: bool,
+    /// Refuse files larger than this many bytes
+    pub max_file_size: Option<u64>,
+    /// Refuse file names that don't match a pattern, with the message to show
+    pub name_rule: Option<(NamePattern, String)>,
+    /// Style of the extra copy of selected paths kept by the dialog
+    pub path_style: PathStyle,
+    /// Refuse selected folders that can't be accessed this way
+    pub folder_access: Option<FolderAccess>,
+    pub client_guid: Option<GUID>,
+    /// Folders added to the navigation pane
+    pub places: Vec<PathBuf>
+}
+
+impl ResolvedFilter {
+    fn patterns(&self) -> Vec<String> {
+        let mut out: Vec<String> = self.spec.split(';').map(|p| p.trim().to_lowercase()).collect();
+        out.sort();
+        out.dedup();
+        out
+    }
+
+    /// Whether both filters match the same set of patterns, ignoring case and order
+    fn same_patterns(&self, other: &Self) -> bool {
+        self.patterns() == other.patterns()
+    }
+}
+
+impl ResolvedConfig {
+    /// Convert the caller's filters into specs, returning the filters to show and the indices of
+    /// any that were removed because an earlier filter has the same set of patterns.
+    pub(crate) fn resolve_filters(filter: Option<&[FileTypeFilter]>, dedup: bool) -> (Vec<ResolvedFilter>, Vec<usize>) {
+        let mut filters: Vec<ResolvedFilter> = vec![];
+        let mut removed = vec![];
+        for (index, v) in filter.unwrap_or_default().iter().enumerate() {
+            let resolved = ResolvedFilter {
+                index,
+                description: v.get_description().to_owned(),
+                spec: v.get_spec()
+            };
+            if dedup && filters.iter().any(|f| f.same_patterns(&resolved)) {
+                removed.push(index);
+            } else {
+                filters.push(resolved);
+            }
+        }
+        (filters, removed)
+    }
+
+    /// Make a set of flag changes on top of the ones already in this configuration
+    pub fn add_options(&mut self, options: &DialogOptions) {
+        self.add_layer(OptionLayer::Call, options)
+    }
+
+    /// Apply option changes on top of the ones so far, recording where they came from
+    pub fn add_layer(&mut self, layer: OptionLayer, options: &DialogOptions) {
+        if options.is_empty() {
+            return;
+        }
+        let set = FILEOPENDIALOGOPTIONS(options.get_set().bits() as _);
+        let clear = FILEOPENDIALOGOPTIONS(options.get_cleared().bits() as _);
+        self.options = (self.options | set) & !clear;
+        self.options_cleared = (self.options_cleared & !set) | clear;
+        if let Some(guid) = options.get_client_guid() {
+            self.client_guid = Some(GUID::from_u128(guid.0));
+        }
+        self.option_layers.push((layer, *options));
+    }
+
+    /// The last layer that set or cleared a flag, which is the one that decided its value
+    pub fn option_source(&self, flag: DialogFlags) -> Option<OptionLayer> {
+        self.option_layers.iter().rev()
+            .find(|(_, o)| o.get_set().contains(flag) || o.get_cleared().contains(flag))
+            .map(|(layer, _)| *layer)
+    }
+
+    /// The flags that are set and cleared, each with the layer that decided it, such as
+    /// `+NO_CHANGE_DIR (preset) +FORCE_SHOW_HIDDEN (manager) -OVERWRITE_PROMPT (call)`
+    pub fn describe_options(&self) -> String {
+        let set = DialogFlags::from_bits(self.options.0);
+        let cleared = DialogFlags::from_bits(self.options_cleared.0);
+        let parts: Vec<String> = set.iter().map(|f| ('+', f)).chain(cleared.iter().map(|f| ('-', f)))
+            .map(|(sign, (name, flag))| match self.option_source(flag) {
+                Some(layer) => format!("{}{} ({})", sign, name, layer),
+                None => format!("{}{}", sign, name)
+            }).collect();
+        match parts.is_empty() {
+            true => "no changes".to_owned(),
+            false => parts.join(" ")
+        }
+    }
+
+    /// Start in the folder of a suggested path, with its file name and matching filter selected
+    pub fn suggest_path(&mut self, path: &SuggestedPath) {
+        if let Some(folder) = path.folder.as_ref() {
+            self.initial_folder = folder.clone();
+            self.initial_folder_source = InitialFolderSource::SuggestedPath;
+        }
+        if let Some(name) = path.file_name.as_ref() {
+            self.suggested_name = Some(name.clone());
+        }
+        if let Some(ext) = path.extension.as_ref() {
+            self.default_extension = Some(ext.clone());
+        }
+        if let Some(index) = path.filter_index {
+            self.filter_index = Some(index);
+        }
+    }
+
+    /// Map a zero-based position in the dialog's dropdown back to the caller's filter index
+    pub fn caller_filter_index(&self, position: usize) -> Option<usize> {
+        self.filters.get(position).map(|f| f.index)
+    }
+
+    /// Map the one-based value from `IFileDialog::GetFileTypeIndex` back to the caller's filter
+    /// index. The shell returns 0 or an arbitrary value when there are no filters, which gives `None`.
+    pub fn selected_filter(&self, file_type_index: u32) -> Option<usize> {
+        (file_type_index as usize).checked_sub(1).and_then(|p| self.caller_filter_index(p))
+    }
+
+    /// A one line summary of the configuration, for logging
+    pub fn describe(&self) -> String {
+        let filters = self.filters.iter().map(|f| f.spec.as_str()).collect::<Vec<_>>().join(", ");
+        let mut out = format!("{:?} dialog \"{}\" in {} ({:?}), filters [{}], options {}",
+            self.kind, self.title, self.initial_folder.display(), self.initial_folder_source, filters, self.describe_options());
+        if !self.removed_filters.is_empty() {
+            out.push_str(&format!(", removed duplicate filters {:?}", self.removed_filters));
+        }
+        out
+    }
+
+    pub(crate) fn is_multiselect(&self) -> bool {
+        DialogFlags::from_bits(self.options.0).contains(DialogFlags::ALLOW_MULTISELECT)
+    }
+
+    /// Whether any restrictions need checking when the user presses OK
+    pub(crate) fn has_selection_checks(&self) -> bool {
+        self.max_file_size.is_some() || self.name_rule.is_some() || self.folder_access.is_some()
+    }
+
+    /// Check a selected path against the configuration's restrictions. This runs in the dialog so
+    /// the user can pick something else, and again once it's closed.
+    pub(crate) fn check_selection(&self, path: &Path) -> Result<(), DialogError> {
+        if let Some(limit) = self.max_file_size
+            && let Ok(meta) = std::fs::metadata(path)
+            && meta.is_file() && meta.len() > limit {
+            return Err(DialogError::FileTooLarge { path: path.to_owned(), size: meta.len(), limit });
+        }
+        if let Some((pattern, message)) = self.name_rule.as_ref()
+            && !path.file_name().is_some_and(|n| pattern.matches(&n.to_string_lossy())) {
+            return Err(DialogError::NameMismatch { path: path.to_owned(), message: message.clone() });
+        }
+        if let Some(access) = self.folder_access
+            && path.is_dir() {
+            FileDialogUtils::probe_folder(path, access == FolderAccess::ReadWrite)
+                .map_err(|source| DialogError::FolderNotAccessible { path: path.to_owned(), source })?;
+        }
+        Ok(())
+    }
+
+    /// Whether a selected path is the name pattern rather than a file
+    pub(crate) fn is_pattern(path: &Path) -> bool {
+        path.file_name().is_some_and(|n| n.to_string_lossy().contains(['*', '?']))
+    }
+
+    pub(crate) fn apply_name(&self, dialog: &IFileDialog) -> Result<(), DialogError> {
+        let name = match (self.suggested_name.as_ref(), self.name_pattern.as_ref()) {
+            (Some(_), Some(_)) => return Err(DialogError::ConflictingOptions("a suggested file name and a name pattern")),
+            (Some(name), None) | (None, Some((name, _))) => name,
+            (None, None) => return Ok(())
+        };
+        let name = wide::to_wide(name)?;
+        unsafe { dialog.SetFileName(PCWSTR(name.as_ptr()))? };
+        Ok(())
+    }
+
+    pub(crate) fn apply(&self, dialog: &IFileDialog) -> Result<(), DialogError> {
+        // Check for conflicts before changing anything
+        if self.suggested_name.is_some() && self.name_pattern.is_some() {
+            return Err(DialogError::ConflictingOptions("a suggested file name and a name pattern"));
+        }
+        // File types. Provide owned allocation for file type strings
+        if !self.filters.is_empty() {
+            let filter_platform: Vec<FileTypeFilterWin32> = self.filters.iter()
+                .map(|v| FileTypeFilterWin32::from_spec(&v.spec, &v.description))
+                .collect::<Result<_, _>>()?;
+            let types: Vec<COMDLG_FILTERSPEC> = filter_platform.iter().map(|v| COMDLG_FILTERSPEC {
+                pszName: v.get_description(),
+                pszSpec: v.get_extension()
+            }).collect();
+            unsafe { dialog.SetFileTypes(types.as_slice())? };
+            // SetFileTypeIndex is one-based
+            if let Some(position) = self.filter_index.and_then(|i| self.filters.iter().position(|f| f.index == i)) {
+                unsafe { dialog.SetFileTypeIndex(position as u32 + 1)? };
+            }
+        }
+        if let Some(guid) = self.client_guid.as_ref() {
+            unsafe { dialog.SetClientGuid(guid)? };
+        }
+        // Options
+        if self.options.0 != 0 || self.options_cleared.0 != 0 {
+            let options = unsafe { dialog.GetOptions()? };
+            unsafe { dialog.SetOptions((options | self.options) & !self.options_cleared)? };
+        }
+        // Window Title
+        let title = wide::to_wide(&self.title)?;
+        unsafe { dialog.SetTitle(PCWSTR(title.as_ptr()))? }
+        // Default folder
+        let default_folder = wide::to_wide_os(self.initial_folder.as_os_str())?;
+        let item: IShellItem = unsafe { SHCreateItemFromParsingName(PCWSTR(default_folder.as_ptr()), None)? };
+        unsafe { dialog.SetDefaultFolder(&item)? };
+        self.apply_name(dialog)?;
+        if let Some(path) = self.save_as_item.as_ref()
+            && let Ok(save) = dialog.cast::<IFileSaveDialog>() {
+            let path = wide::to_wide_os(path.as_os_str())?;
+            let item: IShellItem = unsafe { SHCreateItemFromParsingName(PCWSTR(path.as_ptr()), None)? };
+            unsafe { save.SetSaveAsItem(&item)? };
+        }
+        if let Some(ext) = self.default_extension.as_ref() {
+            let ext = wide::to_wide(ext)?;
+            unsafe { dialog.SetDefaultExtension(PCWSTR(ext.as_ptr()))? };
+        }
+        // Navigation pane
+        for place in &self.places {
+            let place = wide::to_wide_os(place.as_os_str())?;
+            let item: IShellItem = unsafe { SHCreateItemFromParsingName(PCWSTR(place.as_ptr()), None)? };
+            unsafe { dialog.AddPlace(&item, FDAP_BOTTOM)? };
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(extension: &str, description: &str) -> FileTypeFilter {
+        FileTypeFilter::new(extension.to_owned(), description.to_owned())
+    }
+
+    #[test]
+    fn resolve_filters_keeps_everything_without_dedup() {
+        let filters = [filter("png", "PNG"), filter("png", "PNG")];
+        let (resolved, removed) = ResolvedConfig::resolve_filters(Some(&filters), false);
+        assert_eq!(resolved.iter().map(|f| f.index).collect::<Vec<_>>(), vec![0, 1]);
+        assert!(removed.is_empty());
+    }
+
+    #[test]
+    fn resolve_filters_drops_duplicates() {
+        let filters = [
+            filter("png", "PNG images"),
+            filter("dds", "DDS textures"),
+            // same pattern in a different case, with a different description
+            filter("PNG", "Portable Network Graphics"),
+            FileTypeFilter::with_extensions(["png", "dds"], "Textures".to_owned()),
+            // same set of patterns in a different order
+            FileTypeFilter::from_spec("*.dds;*.PNG".to_owned(), "Also textures".to_owned()),
+            filter("tga", "Targa images")
+        ];
+        let (resolved, removed) = ResolvedConfig::resolve_filters(Some(&filters), true);
+        assert_eq!(resolved, vec![
+            ResolvedFilter { index: 0, description: "PNG images".to_owned(), spec: "*.png".to_owned() },
+            ResolvedFilter { index: 1, description: "DDS textures".to_owned(), spec: "*.dds".to_owned() },
+            ResolvedFilter { index: 3, description: "Textures".to_owned(), spec: "*.png;*.dds".to_owned() },
+            ResolvedFilter { index: 5, description: "Targa images".to_owned(), spec: "*.tga".to_owned() }
+        ]);
+        assert_eq!(removed, vec![2, 4]);
+    }
+
+    #[test]
+    fn resolve_filters_without_filters() {
+        let (resolved, removed) = ResolvedConfig::resolve_filters(None, true);
+        assert!(resolved.is_empty());
+        assert!(removed.is_empty());
+    }
+
+    fn config_with(filters: &[FileTypeFilter]) -> ResolvedConfig {
+        let (filters, removed_filters) = ResolvedConfig::resolve_filters(Some(filters), true);
+        ResolvedConfig {
+            kind: DialogKind::Open,
+            title: String::new(),
+            initial_folder: PathBuf::new(),
+            initial_folder_source: InitialFolderSource::ManagerDefault,
+            filters,
+            removed_filters,
+            options: FILEOPENDIALOGOPTIONS(0),
+            options_cleared: FILEOPENDIALOGOPTIONS(0),
+            option_layers: vec![],
+            confirm_overwrite: false,
+            suggested_name: None,
+            save_as_item: None,
+            default_extension: None,
+            filter_index: None,
+            name_pattern: None,
+            verify_writable: false,
+            collect_metadata: false,
+            remember_selection: true,
+            max_file_size: None,
+            name_rule: None,
+            path_style: PathStyle::Native,
+            folder_access: None,
+            client_guid: None,
+            places: vec![]
+        }
+    }
+
+    #[test]
+    fn selected_filter_is_one_based() {
+        let config = config_with(&[filter("png", "PNG images"), filter("dds", "DDS textures")]);
+        assert_eq!(config.selected_filter(0), None);
+        assert_eq!(config.selected_filter(1), Some(0));
+        assert_eq!(config.selected_filter(2), Some(1));
+        assert_eq!(config.caller_filter_index(0), Some(0));
+        assert_eq!(config.caller_filter_index(1), Some(1));
+    }
+
+    #[test]
+    fn selected_filter_out_of_range() {
+        let config = config_with(&[filter("png", "PNG images"), filter("dds", "DDS textures")]);
+        assert_eq!(config.selected_filter(3), None);
+        assert_eq!(config.selected_filter(u32::MAX), None);
+        assert_eq!(config.caller_filter_index(2), None);
+        assert_eq!(config_with(&[]).selected_filter(1), None);
+    }
+
+    #[test]
+    fn selected_filter_after_dedup() {
+        let config = config_with(&[
+            filter("png", "PNG images"),
+            filter("PNG", "Portable Network Graphics"),
+            filter("dds", "DDS textures"),
+            filter("dds", "DDS textures"),
+            filter("tga", "Targa images")
+        ]);
+        assert_eq!(config.removed_filters, vec![1, 3]);
+        // the dropdown shows png, dds, tga
+        assert_eq!(config.selected_filter(1), Some(0));
+        assert_eq!(config.selected_filter(2), Some(2));
+        assert_eq!(config.selected_filter(3), Some(4));
+        assert_eq!(config.selected_filter(4), None);
+        assert_eq!(config.caller_filter_index(1), Some(2));
+    }
+
+    fn decompose(path: &str) -> SuggestedPath {
+        let filters = [filter("png", "PNG images"), filter("json", "JSON files")];
+        SuggestedPath::decompose(Path::new(path), &filters)
+    }
+
+    #[test]
+    fn decompose_bare_name() {
+        assert_eq!(decompose("level3.json"), SuggestedPath {
+            folder: None,
+            file_name: Some("level3.json".to_owned()),
+            extension: Some("json".to_owned()),
+            filter_index: Some(1)
+        });
+    }
+
+    #[test]
+    fn decompose_full_path() {
+        assert_eq!(decompose(r"C:\exports\level3.JSON"), SuggestedPath {
+            folder: Some(PathBuf::from(r"C:\exports")),
+            file_name: Some("level3.JSON".to_owned()),
+            extension: Some("JSON".to_owned()),
+            filter_index: Some(1)
+        });
+    }
+
+    #[test]
+    fn decompose_without_extension() {
+        assert_eq!(decompose(r"C:\exports\level3"), SuggestedPath {
+            folder: Some(PathBuf::from(r"C:\exports")),
+            file_name: Some("level3".to_owned()),
+            extension: None,
+            filter_index: None
+        });
+    }
+
+    #[test]
+    fn decompose_unmatched_extension() {
+        assert_eq!(decompose(r"C:\exports\level3.xyz"), SuggestedPath {
+            folder: Some(PathBuf::from(r"C:\exports")),
+            file_name: Some("level3.xyz".to_owned()),
+            extension: Some("xyz".to_owned()),
+            filter_index: None
+        });
+    }
+
+    #[test]
+    fn decompose_trailing_separator() {
+        let expected = |folder: &str| SuggestedPath { folder: Some(PathBuf::from(folder)), ..Default::default() };
+        assert_eq!(decompose(r"C:\exports\"), expected(r"C:\exports\"));
+        assert_eq!(decompose("C:/exports/"), expected("C:/exports/"));
+        assert_eq!(decompose(r"C:\"), expected(r"C:\"));
+    }
+
+    #[test]
+    fn decompose_empty_path() {
+        assert_eq!(decompose(""), SuggestedPath::default());
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::{ Path, PathBuf };
+use windows::{
+    core::{ BOOL, HRESULT, PCWSTR, w },
+    Win32::{
+        Foundation::HWND,
+        System::LibraryLoader::{ GetProcAddress, LoadLibraryW },
+        UI::{
+            Controls::{
+                TASKDIALOGCONFIG,
+                TASKDIALOG_BUTTON,
+                TD_WARNING_ICON,
+                TDCBF_CANCEL_BUTTON,
+                TDF_ALLOW_DIALOG_CANCELLATION,
+                TDF_POSITION_RELATIVE_TO_WINDOW
+            },
+            WindowsAndMessaging::{
+                IDNO,
+                IDYES,
+                MB_ICONWARNING,
+                MB_YESNOCANCEL,
+                MessageBoxW
+            }
+        }
+    }
+};
+use crate::utils::wide;
+use super::DialogError;
+
+/// What to do when a save dialog's selection already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwriteChoice {
+    Overwrite,
+    /// Save under a new name next to the existing file, see [`unique_path`]
+    KeepBoth,
+    Cancel
+}
+
+type TaskDialogIndirectFn = unsafe extern "system" fn(
+    *const TASKDIALOGCONFIG, *mut i32, *mut i32, *mut BOOL
+) -> HRESULT;
+
+const ID_OVERWRITE: i32 = 100;
+const ID_KEEP_BOTH: i32 = 101;
+
+/// Ask the user whether to overwrite an existing file, keep both or cancel. This uses a task
+/// dialog when version 6 of the common controls is available and falls back to a message box
+/// otherwise.
+pub fn confirm_overwrite(owner: Option<HWND>, path: &Path) -> Result<OverwriteChoice, DialogError> {
+    let name = path.file_name().map_or(path.as_os_str(), |v| v);
+    let instruction = wide::to_wide(&format!("{} already exists", name.to_string_lossy()))?;
+    let content = wide::to_wide(&format!("Folder: {}", path.parent().unwrap_or(path).display()))?;
+    let title = wide::to_wide("Confirm Save As")?;
+    match task_dialog_indirect() {
+        Some(show) => {
+            let overwrite = wide::to_wide("Overwrite the existing file")?;
+            let keep_both = wide::to_wide("Keep both files")?;
+            // The config only borrows these, so they must outlive the TaskDialogIndirect call
+            let buttons = [
+                TASKDIALOG_BUTTON { nButtonID: ID_OVERWRITE, pszButtonText: PCWSTR(overwrite.as_ptr()) },
+                TASKDIALOG_BUTTON { nButtonID: ID_KEEP_BOTH, pszButtonText: PCWSTR(keep_both.as_ptr()) }
+            ];
+            let mut config = TASKDIALOGCONFIG {
+                cbSize: size_of::<TASKDIALOGCONFIG>() as u32,
+                hwndParent: owner.unwrap_or_default(),
+                dwFlags: TDF_ALLOW_DIALOG_CANCELLATION | TDF_POSITION_RELATIVE_TO_WINDOW,
+                dwCommonButtons: TDCBF_CANCEL_BUTTON,
+                pszWindowTitle: PCWSTR(title.as_ptr()),
+                pszMainInstruction: PCWSTR(instruction.as_ptr()),
+                pszContent: PCWSTR(content.as_ptr()),
+                cButtons: buttons.len() as u32,
+                pButtons: buttons.as_ptr(),
+                nDefaultButton: ID_KEEP_BOTH,
+                ..Default::default()
+            };
+            config.Anonymous1.pszMainIcon = TD_WARNING_ICON;
+            let mut button = 0;
+            unsafe { show(&config, &mut button, std::ptr::null_mut(), std::ptr::null_mut()).ok()? };
+            Ok(match button {
+                ID_OVERWRITE => OverwriteChoice::Overwrite,
+                ID_KEEP_BOTH => OverwriteChoice::KeepBoth,
+                _ => OverwriteChoice::Cancel
+            })
+        },
+        None => {
+            let text = wide::to_wide(&format!("{} already exists.\n\nYes: overwrite the existing file\nNo: keep both files",
+                name.to_string_lossy()))?;
+            let result = unsafe { MessageBoxW(owner, PCWSTR(text.as_ptr()), PCWSTR(title.as_ptr()), MB_YESNOCANCEL | MB_ICONWARNING) };
+            Ok(match result {
+                IDYES => OverwriteChoice::Overwrite,
+                IDNO => OverwriteChoice::KeepBoth,
+                _ => OverwriteChoice::Cancel
+            })
+        }
+    }
+}
+
+/// Find a name next to `path` that doesn't exist yet, in the same style as Explorer:
+/// `level3.json` becomes `level3 (2).json`, then `level3 (3).json` and so on.
+pub fn unique_path(path: &Path) -> PathBuf {
+    let stem = path.file_stem().map(|v| v.to_string_lossy().into_owned()).unwrap_or_default();
+    let extension = path.extension().map(|v| format!(".{}", v.to_string_lossy())).unwrap_or_default();
+    (2..).map(|i| path.with_file_name(format!("{} ({}){}", stem, i, extension)))
+        .find(|p| !p.exists())
+        .unwrap()
+}
+
+// TaskDialogIndirect is only exported by comctl32 6.0, which processes only get with a manifest.
+// Linking it directly would stop the process from loading without one.
+fn task_dialog_indirect() -> Option<TaskDialogIndirectFn> {
+    let module = unsafe { LoadLibraryW(w!("comctl32.dll")).ok()? };
+    let proc = unsafe { GetProcAddress(module, windows::core::s!("TaskDialogIndirect"))? };
+    Some(unsafe { std::mem::transmute::<unsafe extern "system" fn() -> isize, TaskDialogIndirectFn>(proc) })
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf
+};
+use super::{ AuditLog, FileDialogManager, FolderHistory, ManagerError };
+
+// The manager's remembered state when it was copied, to tell what the dialog changed
+struct Snapshot {
+    default_open: PathBuf,
+    default_save: PathBuf,
+    open_contexts: HashMap<String, PathBuf>,
+    save_contexts: HashMap<String, PathBuf>,
+    history: FolderHistory
+}
+
+impl FileDialogManager {
+    /// Run a function with a copy of the global manager, without holding its lock. Dialogs are
+    /// modal, so showing one through [`FileDialogManager::with`] blocks every other thread that
+    /// uses the manager until it closes. This copies the manager out, releases the lock while `f`
+    /// runs, then locks it again briefly to write back what changed: remembered folders, history,
+    /// audit entries and client GUIDs. Other changes made to the copy are discarded.
+    ///
+    /// An owner set with a callback is shared with the copy and called when the copy shows a
+    /// dialog, without the lock held.
+    pub fn with_unlocked<F, R>(f: F) -> Result<R, ManagerError>
+    where F: FnOnce(&mut FileDialogManager) -> R {
+        let (mut copy, before) = Self::with_ref(|m| (m.detach(), m.snapshot()))?;
+        let out = f(&mut copy);
+        // the global manager may have been removed while the dialog was open, which leaves
+        // nothing to write back to
+        let _ = Self::with(|m| m.write_back(before, copy));
+        Ok(out)
+    }
+
+    fn detach(&self) -> Self {
+        let mut out = self.get_config().build();
+        // a callback is called by the copy when it shows a dialog, after the lock is released,
+        // so it can use the manager itself
+        out.owner = self.owner.clone();
+        out.initial_open = self.initial_open.clone();
+        out.initial_save = self.initial_save.clone();
+        out.open_contexts = self.open_contexts.clone();
+        out.save_contexts = self.save_contexts.clone();
+        out.client_guids = self.client_guids.clone();
+        // entries are recorded into the global log when writing back
+        let mut audit_log = AuditLog::default();
+        audit_log.set_limit(self.audit_log.get_limit());
+        audit_log.set_verbose(self.audit_log.get_verbose());
+        out.audit_log = audit_log;
+        // saved once the changes are written back
+        out.state_file = None;
+        out
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            default_open: self.default_open.clone(),
+            default_save: self.default_save.clone(),
+            open_contexts: self.open_contexts.clone(),
+            save_contexts: self.save_contexts.clone(),
+            history: self.history.clone()
+        }
+    }
+
+    // Only fields that the copy changed are written, so changes made by other threads while the
+    // dialog was open are kept
+    fn write_back(&mut self, before: Snapshot, copy: Self) {
+        let mut remembered = false;
+        if copy.default_open != before.default_open {
+            self.default_open = copy.default_open;
+            remembered = true;
+        }
+        if copy.default_save != before.default_save {
+            self.default_save = copy.default_save;
+            remembered = true;
+        }
+        for (key, path) in copy.open_contexts {
+            if before.open_contexts.get(&key) != Some(&path) {
+                self.open_contexts.insert(key, path);
+                remembered = true;
+            }
+        }
+        for (key, path) in copy.save_contexts {
+            if before.save_contexts.get(&key) != Some(&path) {
+                self.save_contexts.insert(key, path);
+                remembered = true;
+            }
+        }
+        // merged rather than replaced, so folders recorded or pinned by other threads while the
+        // dialog was open are kept
+        for folder in copy.history.recorded_since(&before.history) {
+            self.history.record(folder);
+        }
+        for entry in copy.audit_log.get_entries() {
+            self.audit_log.record(entry.clone());
+        }
+        for guid in copy.client_guids {
+            self.register_client_guid(guid);
+        }
+        if remembered {
+            self.autosave_state();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ sync::{ PoisonError, mpsc }, thread, time::Duration };
+    use windows::Win32::Foundation::HWND;
+    use super::super::{ OwnerSource, tests::GLOBAL };
+    use super::*;
+
+    fn default_open() -> Result<PathBuf, ManagerError> {
+        FileDialogManager::with_ref(|m| m.get_default_open().to_owned())
+    }
+
+    // Run on another thread, failing if it doesn't finish because the manager stayed locked
+    fn on_other_thread<F, R>(f: F) -> R where F: FnOnce() -> R + Send + 'static, R: Send + 'static {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || { let _ = sender.send(f()); });
+        receiver.recv_timeout(Duration::from_secs(5)).expect("the manager was locked while the dialog was open")
+    }
+
+    #[test]
+    fn manager_is_unlocked_while_dialog_is_open() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let folder = std::env::temp_dir().join("riri-file-dialog-detach");
+        FileDialogManager::new_with_owner(folder.clone(), OwnerSource::Callback(Box::new(|| {
+            // reading the manager from the owner callback deadlocked when it was called under the lock
+            default_open().ok()?;
+            Some(HWND(0x1234 as _))
+        })));
+        let chosen = folder.join("models").join("cammy.gmd");
+        let saved = folder.join("exports");
+        // stands in for showing a dialog through the copy
+        let (owner, seen) = FileDialogManager::with_unlocked(|m| {
+            let owner = m.get_owner().and_then(OwnerSource::resolve);
+            let seen = on_other_thread(default_open);
+            let other = saved.clone();
+            on_other_thread(move || FileDialogManager::with(|m| m.set_default_save(other))).unwrap();
+            m.set_default_open(&chosen);
+            (owner, seen)
+        }).unwrap();
+        assert_eq!(owner, Some(HWND(0x1234 as _)));
+        assert_eq!(seen, Ok(folder));
+        // the copy's selection is written back, and the change made by the other thread is kept
+        assert_eq!(default_open(), Ok(chosen));
+        assert_eq!(FileDialogManager::with_ref(|m| m.get_default_save().to_owned()), Ok(saved));
+        *FileDialogManager::lock() = None;
+    }
+
+    #[test]
+    fn history_is_merged() {
+        let _global = GLOBAL.lock().unwrap_or_else(PoisonError::into_inner);
+        FileDialogManager::new_deferred(std::env::temp_dir().join("riri-file-dialog-detach"));
+        FileDialogManager::with(|m| m.get_history_mut().record("before")).unwrap();
+        FileDialogManager::with_unlocked(|m| {
+            on_other_thread(|| FileDialogManager::with(|m| {
+                m.get_history_mut().record("other");
+                m.get_history_mut().pin("pinned");
+            })).unwrap();
+            m.get_history_mut().record("picked");
+        }).unwrap();
+        let history = FileDialogManager::with_ref(|m| m.get_history().clone()).unwrap();
+        let recent: Vec<&str> = history.get_recent().iter().map(|v| v.to_str().unwrap()).collect();
+        assert_eq!(recent, ["picked", "other", "before"]);
+        assert_eq!(history.get_pinned(), [PathBuf::from("pinned")]);
+        *FileDialogManager::lock() = None;
+    }
+}
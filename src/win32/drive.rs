@@ -0,0 +1,113 @@
+use std::path::{ Path, PathBuf };
+use windows::{
+    core::HSTRING,
+    Win32::{
+        Storage::FileSystem::{ GetDiskFreeSpaceExW, GetDriveTypeW, GetVolumeInformationW },
+        System::WindowsProgramming::{
+            DRIVE_CDROM,
+            DRIVE_FIXED,
+            DRIVE_NO_ROOT_DIR,
+            DRIVE_RAMDISK,
+            DRIVE_REMOTE,
+            DRIVE_REMOVABLE
+        },
+        UI::Shell::{
+            FOLDERID_ComputerFolder,
+            IShellItem,
+            KF_FLAG_DEFAULT,
+            SHGetKnownFolderItem
+        }
+    }
+};
+use crate::utils::wide;
+use super::{
+    DialogError,
+    DialogKind,
+    FileDialog,
+    OpenDialog,
+    events::DialogEvents,
+    pipeline::DialogPipeline
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveKind {
+    Unknown,
+    Removable,
+    Fixed,
+    /// A mapped network drive
+    Remote,
+    CdRom,
+    RamDisk
+}
+
+impl DriveKind {
+    fn from_drive_type(value: u32) -> Option<Self> {
+        match value {
+            DRIVE_NO_ROOT_DIR => None,
+            DRIVE_REMOVABLE => Some(Self::Removable),
+            DRIVE_FIXED => Some(Self::Fixed),
+            DRIVE_REMOTE => Some(Self::Remote),
+            DRIVE_CDROM => Some(Self::CdRom),
+            DRIVE_RAMDISK => Some(Self::RamDisk),
+            _ => Some(Self::Unknown)
+        }
+    }
+
+    /// Get the kind of drive that a root path (such as `C:\`) refers to. Returns `None` if the
+    /// path isn't the root of a drive.
+    pub fn of_root<P>(root: P) -> Option<Self> where P: AsRef<Path> {
+        let root = root.as_ref();
+        if root.parent().is_some() {
+            return None;
+        }
+        Self::from_drive_type(unsafe { GetDriveTypeW(&HSTRING::from(root)) })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DriveSelection {
+    /// The root directory, such as `C:\`
+    pub root: PathBuf,
+    pub letter: Option<char>,
+    pub label: String,
+    pub kind: DriveKind,
+    /// Free space available to the current user, in bytes
+    pub free_space: Option<u64>
+}
+
+impl DriveSelection {
+    pub fn query<P>(root: P) -> Option<Self> where P: AsRef<Path> {
+        let root = root.as_ref();
+        let kind = DriveKind::of_root(root)?;
+        let root_wide = HSTRING::from(root);
+        let mut label = [0u16; 261];
+        let label = match unsafe { GetVolumeInformationW(&root_wide, Some(&mut label), None, None, None, None) } {
+            Ok(_) => wide::from_wide(&label).to_string_lossy().into_owned(),
+            Err(_) => String::new()
+        };
+        let mut free = 0;
+        let free_space = unsafe { GetDiskFreeSpaceExW(&root_wide, Some(&mut free), None, None) }
+            .ok().map(|_| free);
+        let letter = root.to_str().and_then(|v| v.chars().next()).filter(|c| c.is_ascii_alphabetic());
+        Some(Self { root: root.to_owned(), letter, label, kind, free_space })
+    }
+}
+
+impl<'a> OpenDialog<'a> {
+    /// Ask the user to pick a drive. The dialog starts in This PC and can't navigate anywhere
+    /// else, so only drive roots can be selected.
+    pub fn pick_drive(&mut self, title: Option<&str>, include_network: bool) -> Result<Option<DriveSelection>, DialogError> {
+        let mut config = self.resolve_config(DialogKind::Drive, None, title);
+        self.settings.apply(&mut config);
+        let computer: IShellItem = unsafe { SHGetKnownFolderItem(&FOLDERID_ComputerFolder, KF_FLAG_DEFAULT, None)? };
+        unsafe { self.handle.SetFolder(&computer)? };
+        let events = DialogEvents::new()
+            .restrict_to_folder(computer)
+            .validator(Box::new(move |path| match DriveKind::of_root(path) {
+                Some(DriveKind::Remote) if !include_network => Err("Network drives can't be selected".to_owned()),
+                Some(_) => Ok(()),
+                None => Err("Select a drive".to_owned())
+            }));
+        Ok(self.run(&config, events)?.and_then(DriveSelection::query))
+    }
+}
@@ -0,0 +1,246 @@
+use std::{
+    cell::Cell,
+    ffi::OsStr,
+    path::{ Path, PathBuf }
+};
+use windows::{
+    core::{ implement, Interface, PCWSTR, Ref, Result as WinResult },
+    Win32::{
+        Foundation::{ E_ABORT, HWND, S_FALSE },
+        System::{ Com::CoTaskMemFree, Ole::IOleWindow },
+        UI::{
+            Shell::{
+                FDE_OVERWRITE_RESPONSE,
+                FDE_SHAREVIOLATION_RESPONSE,
+                FDEOR_DEFAULT,
+                FDESVR_DEFAULT,
+                IFileDialog,
+                IFileDialogEvents,
+                IFileDialogEvents_Impl,
+                IFileOpenDialog,
+                IShellItem,
+                SICHINT_CANONICAL,
+                SIGDN_DESKTOPABSOLUTEPARSING
+            },
+            WindowsAndMessaging::{ MB_ICONWARNING, MB_OK, MessageBoxW }
+        }
+    }
+};
+use crate::utils::wide;
+use super::{ MonitorSelection, pipeline };
+
+/// Checks a selected path when the user presses OK, returning a message to show them if the
+/// selection should be refused.
+pub(crate) type SelectionValidator = Box<dyn Fn(&Path) -> Result<(), String>>;
+
+/// Receives notifications from a dialog while it's being shown. This is used for anything that
+/// needs the dialog's own window, which only exists once `Show` has been called, or that needs to
+/// refuse navigation or selections.
+#[implement(IFileDialogEvents)]
+pub(crate) struct DialogEvents {
+    owner: Option<HWND>,
+    monitor: Option<MonitorSelection>,
+    // only allow navigating to this folder
+    allowed_folder: Option<IShellItem>,
+    // only allow navigating to and selecting items inside this folder, by desktop parsing name
+    allowed_tree: Option<String>,
+    validators: Vec<SelectionValidator>,
+    // OnFolderChange is first called once the dialog window has been created
+    opened: Cell<bool>
+}
+
+impl DialogEvents {
+    pub(crate) fn new() -> Self {
+        Self { owner: None, monitor: None, allowed_folder: None, allowed_tree: None, validators: vec![], opened: Cell::new(false) }
+    }
+
+    pub(crate) fn owner(mut self, value: Option<HWND>) -> Self {
+        self.owner = value;
+        self
+    }
+
+    pub(crate) fn monitor(mut self, value: Option<MonitorSelection>) -> Self {
+        self.monitor = value;
+        self
+    }
+
+    pub(crate) fn restrict_to_folder(mut self, value: IShellItem) -> Self {
+        self.allowed_folder = Some(value);
+        self
+    }
+
+    /// Keep navigation and the selection inside a folder, including its subfolders. Unlike
+    /// [`DialogEvents::validator`] this works for items without a file system path, such as
+    /// entries inside a zip file.
+    #[cfg(feature = "archive")]
+    pub(crate) fn restrict_to_tree(mut self, root: String) -> Self {
+        self.allowed_tree = Some(root);
+        self
+    }
+
+    pub(crate) fn validator(mut self, value: SelectionValidator) -> Self {
+        self.validators.push(value);
+        self
+    }
+
+    /// If there's nothing to do, the dialog can be shown without an event handler
+    pub(crate) fn is_empty(&self) -> bool {
+        self.monitor.is_none() && self.allowed_folder.is_none() && self.allowed_tree.is_none() && self.validators.is_empty()
+    }
+
+    fn get_window(dialog: &IFileDialog) -> Option<HWND> {
+        match dialog.cast::<IOleWindow>().and_then(|w| unsafe { w.GetWindow() }) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                log::warn!("Couldn't get the dialog's window: {}", e);
+                None
+            }
+        }
+    }
+
+    fn on_open(&self, dialog: &IFileDialog) {
+        if let Some(monitor) = self.monitor && let Some(window) = Self::get_window(dialog) {
+            monitor.move_dialog(self.owner, window);
+        }
+    }
+
+    /// Get every item that's currently selected, as file system paths
+    pub(crate) fn get_selected_paths(dialog: &IFileDialog) -> WinResult<Vec<PathBuf>> {
+        Self::selected_items(dialog)?.iter().map(pipeline::item_path).collect()
+    }
+
+    fn in_tree(&self, item: &IShellItem) -> bool {
+        let Some(root) = self.allowed_tree.as_ref() else {
+            return true;
+        };
+        match parsing_name(item) {
+            Ok(name) => is_within(root, &name),
+            Err(e) => {
+                log::warn!("Couldn't get the name of a dialog item: {}", e);
+                false
+            }
+        }
+    }
+
+    fn selected_items(dialog: &IFileDialog) -> WinResult<Vec<IShellItem>> {
+        match dialog.cast::<IFileOpenDialog>() {
+            Ok(open) => {
+                let results = unsafe { open.GetResults()? };
+                let count = unsafe { results.GetCount()? };
+                (0..count).map(|i| unsafe { results.GetItemAt(i) }).collect()
+            },
+            Err(_) => Ok(vec![unsafe { dialog.GetResult()? }])
+        }
+    }
+
+    fn refuse(dialog: &IFileDialog, message: &str, caption: &OsStr) -> WinResult<()> {
+        let text = wide::to_wide(message).unwrap_or(vec![0]);
+        let caption = wide::to_wide_os(caption).unwrap_or(vec![0]);
+        unsafe { MessageBoxW(Self::get_window(dialog), PCWSTR(text.as_ptr()), PCWSTR(caption.as_ptr()), MB_OK | MB_ICONWARNING) };
+        // S_FALSE keeps the dialog open
+        Err(S_FALSE.into())
+    }
+
+    fn validate(&self, dialog: &IFileDialog) -> WinResult<()> {
+        if let Some(root) = self.allowed_tree.as_ref() {
+            for item in Self::selected_items(dialog)? {
+                if !self.in_tree(&item) {
+                    return Self::refuse(dialog, &format!("Choose a file inside {}", root), OsStr::new(root));
+                }
+            }
+        }
+        if self.validators.is_empty() {
+            return Ok(());
+        }
+        for path in Self::get_selected_paths(dialog)? {
+            for validator in &self.validators {
+                if let Err(message) = validator(&path) {
+                    return Self::refuse(dialog, &message, path.as_os_str());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An item's desktop parsing name, which items inside archives have even though they don't have
+/// a file system path
+pub(crate) fn parsing_name(item: &IShellItem) -> WinResult<String> {
+    let name = unsafe { item.GetDisplayName(SIGDN_DESKTOPABSOLUTEPARSING)? };
+    let out = unsafe { wide::from_wide_until_nul(name.0) };
+    unsafe { CoTaskMemFree(Some(name.0 as _)) }
+    Ok(out.to_string_lossy().into_owned())
+}
+
+/// Whether a parsing name is `root` or something inside it, ignoring ASCII case
+pub(crate) fn is_within(root: &str, name: &str) -> bool {
+    let root = root.trim_end_matches('\\');
+    match name.get(..root.len()) {
+        Some(start) if start.eq_ignore_ascii_case(root) => {
+            let rest = &name[root.len()..];
+            rest.is_empty() || rest.starts_with('\\')
+        },
+        _ => false
+    }
+}
+
+impl IFileDialogEvents_Impl for DialogEvents_Impl {
+    fn OnFileOk(&self, pfd: Ref<'_, IFileDialog>) -> WinResult<()> {
+        match pfd.as_ref() {
+            Some(dialog) => self.validate(dialog),
+            None => Ok(())
+        }
+    }
+
+    fn OnFolderChanging(&self, _: Ref<'_, IFileDialog>, psifolder: Ref<'_, IShellItem>) -> WinResult<()> {
+        if let Some(allowed) = self.allowed_folder.as_ref() && let Some(folder) = psifolder.as_ref()
+        && !matches!(unsafe { allowed.Compare(folder, SICHINT_CANONICAL.0 as u32) }, Ok(0)) {
+            // returning an error prevents navigation
+            return Err(E_ABORT.into());
+        }
+        if let Some(folder) = psifolder.as_ref() && !self.in_tree(folder) {
+            return Err(E_ABORT.into());
+        }
+        Ok(())
+    }
+
+    fn OnFolderChange(&self, pfd: Ref<'_, IFileDialog>) -> WinResult<()> {
+        if !self.opened.replace(true) && let Some(dialog) = pfd.as_ref() {
+            self.on_open(dialog);
+        }
+        Ok(())
+    }
+
+    fn OnSelectionChange(&self, _: Ref<'_, IFileDialog>) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnShareViolation(&self, _: Ref<'_, IFileDialog>, _: Ref<'_, IShellItem>) -> WinResult<FDE_SHAREVIOLATION_RESPONSE> {
+        Ok(FDESVR_DEFAULT)
+    }
+
+    fn OnTypeChange(&self, _: Ref<'_, IFileDialog>) -> WinResult<()> {
+        Ok(())
+    }
+
+    fn OnOverwrite(&self, _: Ref<'_, IFileDialog>, _: Ref<'_, IShellItem>) -> WinResult<FDE_OVERWRITE_RESPONSE> {
+        Ok(FDEOR_DEFAULT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn within_tree() {
+        let root = r"C:\mods\pack.zip";
+        assert!(is_within(root, r"C:\mods\pack.zip"));
+        assert!(is_within(root, r"C:\mods\pack.zip\models\a.gmd"));
+        assert!(is_within(r"C:\mods\pack.zip\", r"C:\MODS\Pack.zip\a.gmd"));
+        assert!(!is_within(root, r"C:\mods\pack.zip2\a.gmd"));
+        assert!(!is_within(root, r"C:\mods\a.gmd"));
+        assert!(!is_within(root, r"C:\mods"));
+        assert!(!is_within(root, r"D:\other\pack.zip\a.gmd"));
+    }
+}
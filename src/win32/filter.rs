@@ -0,0 +1,532 @@
+use std::{
+    error::Error,
+    fmt::{ Debug, Display, Formatter },
+    hash::{ Hash, Hasher }
+};
+#[cfg(feature = "serde")]
+use std::path::Path;
+use windows::core::PCWSTR;
+use crate::utils::wide::{ self, InteriorNulError };
+
+/// A file type shown in the dialog's type dropdown, matching one or more extensions. Two filters
+/// are equal when their descriptions match and their extensions match ignoring ASCII case, since
+/// Windows treats `PNG` and `png` as the same extension.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "FileTypeFilterDef", into = "FileTypeFilterDef"))]
+pub struct FileTypeFilter {
+    // Extensions are stored as given and normalized when read, which lets new stay a const fn.
+    // For filters made with from_spec this is the whole pattern.
+    extension: String,
+    // any further extensions, see with_extensions
+    more_extensions: Vec<String>,
+    description: String,
+    raw: bool
+}
+
+/// How [`FileTypeFilter::for_extensions`] turns a list of extensions into filters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtensionGrouping {
+    /// One filter per extension, in the given order
+    #[default]
+    Separate,
+    /// A single filter that matches every extension
+    Combined
+}
+
+// Descriptions for extensions that are likely to come up, anything else is "XYZ files"
+const EXTENSION_NAMES: [(&str, &str); 24] = [
+    ("bmp", "Bitmap images"),
+    ("csv", "CSV files"),
+    ("dds", "DDS textures"),
+    ("exr", "OpenEXR images"),
+    ("fbx", "FBX models"),
+    ("gif", "GIF images"),
+    ("glb", "glTF binary models"),
+    ("gltf", "glTF models"),
+    ("ini", "Configuration files"),
+    ("jpeg", "JPEG images"),
+    ("jpg", "JPEG images"),
+    ("json", "JSON files"),
+    ("mp3", "MP3 audio"),
+    ("obj", "OBJ models"),
+    ("ogg", "Ogg audio"),
+    ("png", "PNG images"),
+    ("tga", "Targa images"),
+    ("tif", "TIFF images"),
+    ("tiff", "TIFF images"),
+    ("toml", "TOML files"),
+    ("txt", "Text files"),
+    ("wav", "WAV audio"),
+    ("xml", "XML files"),
+    ("zip", "ZIP archives")
+];
+
+/// Why an extension couldn't be used for a [`FileTypeFilter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterError {
+    /// Nothing was left after removing a leading `*.` or `.`
+    EmptyExtension,
+    /// The extension contains a wildcard, path separator or `;` after normalization
+    InvalidCharacter {
+        extension: String,
+        character: char
+    }
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyExtension => write!(f, "filter extension is empty"),
+            Self::InvalidCharacter { extension, character } =>
+                write!(f, "filter extension \"{}\" can't contain '{}'", extension, character)
+        }
+    }
+}
+
+impl Error for FilterError {}
+
+impl FileTypeFilter {
+    /// Create a filter for a single extension. A leading `*.` or `.` is ignored, so `"png"`,
+    /// `".png"` and `"*.png"` all produce the spec `*.png`. Use [`FileTypeFilter::try_new`] to
+    /// reject extensions that would still produce a broken spec.
+    pub const fn new(extension: String, description: String) -> Self {
+        Self { extension, more_extensions: Vec::new(), description, raw: false }
+    }
+
+    /// Create a filter that matches several extensions, such as `"Image files"` for `png`, `jpg`
+    /// and `bmp`, which produces the spec `*.png;*.jpg;*.bmp`. Extensions are normalized like
+    /// [`FileTypeFilter::new`].
+    pub fn with_extensions<I, S>(extensions: I, description: String) -> Self
+    where I: IntoIterator<Item = S>, S: AsRef<str> {
+        let mut extensions = extensions.into_iter().map(|e| e.as_ref().to_owned());
+        // no extensions leaves the first one empty, which try_with_extensions rejects
+        let extension = extensions.next().unwrap_or_default();
+        Self { extension, more_extensions: extensions.collect(), description, raw: false }
+    }
+
+    /// A filter that shows every file, described as `All files (*.*)`
+    pub fn all_files() -> Self {
+        Self::from_spec("*.*".to_owned(), "All files (*.*)".to_owned())
+    }
+
+    /// Create a filter with a generated description, such as `PNG images (*.png)`. Extensions
+    /// the crate doesn't know about are described as `XYZ files (*.xyz)`.
+    pub fn for_extension(extension: &str) -> Self {
+        let extension = Self::normalize_extension(extension);
+        let name = EXTENSION_NAMES.iter()
+            .find(|(e, _)| e.eq_ignore_ascii_case(extension))
+            .map_or_else(|| format!("{} files", extension.to_uppercase()), |(_, n)| n.to_string());
+        Self::new(extension.to_owned(), format!("{} (*.{})", name, extension))
+    }
+
+    /// Create filters for a list of extensions with generated descriptions, either one for each
+    /// extension (see [`FileTypeFilter::for_extension`]) or one covering all of them, described as
+    /// `Supported files (*.png;*.dds)`.
+    pub fn for_extensions(extensions: &[&str], grouping: ExtensionGrouping) -> Vec<Self> {
+        match grouping {
+            ExtensionGrouping::Separate => extensions.iter().map(|e| Self::for_extension(e)).collect(),
+            ExtensionGrouping::Combined if extensions.is_empty() => vec![],
+            ExtensionGrouping::Combined => {
+                let mut out = Self::with_extensions(extensions, String::new());
+                out.description = format!("Supported files ({})", out.get_spec());
+                vec![out]
+            }
+        }
+    }
+
+    /// Like [`FileTypeFilter::new`], but fails if the extension is empty or contains a wildcard,
+    /// path separator or `;` once normalized.
+    pub fn try_new(extension: String, description: String) -> Result<Self, FilterError> {
+        Self::new(extension, description).validate()
+    }
+
+    /// Like [`FileTypeFilter::with_extensions`], but fails if there are no extensions or any of
+    /// them wouldn't be accepted by [`FileTypeFilter::try_new`]
+    pub fn try_with_extensions<I, S>(extensions: I, description: String) -> Result<Self, FilterError>
+    where I: IntoIterator<Item = S>, S: AsRef<str> {
+        Self::with_extensions(extensions, description).validate()
+    }
+
+    fn validate(self) -> Result<Self, FilterError> {
+        for extension in self.extensions() {
+            if extension.is_empty() {
+                return Err(FilterError::EmptyExtension);
+            }
+            if let Some(character) = extension.chars().find(|c| matches!(c, '*' | '?' | '/' | '\\' | ';')) {
+                return Err(FilterError::InvalidCharacter { extension: extension.to_owned(), character });
+            }
+        }
+        Ok(self)
+    }
+
+    /// Whether [`FileTypeFilter::try_new`] would accept an extension. This can be used in
+    /// constants, which is how [`define_dialog!`](crate::define_dialog) checks extensions.
+    pub const fn is_valid_extension(extension: &str) -> bool {
+        let mut bytes = extension.trim_ascii().as_bytes();
+        if let [b'*', b'.', rest @ ..] | [b'.', rest @ ..] = bytes {
+            bytes = rest;
+        }
+        let mut i = 0;
+        while i < bytes.len() {
+            if matches!(bytes[i], b'*' | b'?' | b'/' | b'\\' | b';') {
+                return false;
+            }
+            i += 1;
+        }
+        !bytes.is_empty()
+    }
+
+    /// Create a filter from a pattern that's passed to the dialog unchanged, such as
+    /// `"*.tar.gz"` or `"save_??.dat"`.
+    pub const fn from_spec(spec: String, description: String) -> Self {
+        Self { extension: spec, more_extensions: Vec::new(), description, raw: true }
+    }
+
+    /// Remove a leading `*.` or `.` from an extension
+    pub fn normalize_extension(extension: &str) -> &str {
+        let extension = extension.trim();
+        extension.strip_prefix("*.").or_else(|| extension.strip_prefix('.')).unwrap_or(extension)
+    }
+
+    // The normalized extensions, or the whole pattern for raw specs
+    fn extensions(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(&self.extension).chain(&self.more_extensions).map(|e| match self.raw {
+            true => e.as_str(),
+            false => Self::normalize_extension(e)
+        })
+    }
+
+    /// The first normalized extension, or the whole pattern for filters made with [`FileTypeFilter::from_spec`]
+    pub fn get_extension(&self) -> &str { self.extensions().next().unwrap_or_default() }
+    pub fn get_extensions(&self) -> Vec<&str> { self.extensions().collect() }
+    pub fn get_description(&self) -> &str { &self.description }
+    pub fn is_raw_spec(&self) -> bool { self.raw }
+
+    /// The pattern given to the dialog for this filter
+    pub fn get_spec(&self) -> String {
+        match self.raw {
+            true => self.extensions().collect::<Vec<_>>().join(";"),
+            false => self.extensions().map(|e| format!("*.{}", e)).collect::<Vec<_>>().join(";")
+        }
+    }
+
+    /// Whether a file with this extension (with or without a leading dot) would be shown by the
+    /// filter, ignoring case. Always false for raw specs.
+    pub fn matches_extension(&self, extension: &str) -> bool {
+        let extension = Self::normalize_extension(extension);
+        !self.raw && self.extensions().any(|e| e.eq_ignore_ascii_case(extension))
+    }
+}
+
+impl PartialEq for FileTypeFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.raw == other.raw
+            && self.extensions().count() == other.extensions().count()
+            && self.extensions().zip(other.extensions()).all(|(a, b)| a.eq_ignore_ascii_case(b))
+            && self.description == other.description
+    }
+}
+
+impl Eq for FileTypeFilter {}
+
+impl Hash for FileTypeFilter {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for extension in self.extensions() {
+            for b in extension.bytes() {
+                state.write_u8(b.to_ascii_lowercase());
+            }
+            state.write_u8(0xff);
+        }
+        self.description.hash(state);
+        self.raw.hash(state);
+    }
+}
+
+impl Display for FileTypeFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let spec = self.get_spec();
+        // generated descriptions such as "PNG images (*.png)" already end with the spec
+        match self.description.ends_with(&format!("({})", spec)) {
+            true => write!(f, "{}", self.description),
+            false => write!(f, "{} ({})", self.description, spec)
+        }
+    }
+}
+
+pub struct FileTypeFilterWin32 {
+    extension: Vec<u16>,
+    description: Vec<u16>
+}
+
+// Like wide::to_wide, but a NUL ends the string early instead of failing, as it always did for
+// FileTypeFilterWin32::new
+fn to_wide_lossy(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+impl FileTypeFilterWin32 {
+    /// Create a filter for the spec `*.{extension}`, ignoring a leading `*.` or `.`. A NUL in
+    /// either string cuts it short, use [`FileTypeFilterWin32::try_new`] to reject them instead.
+    pub fn new(extension: &str, description: &str) -> Self {
+        let extension = to_wide_lossy(&format!("*.{}", FileTypeFilter::normalize_extension(extension)));
+        let description = to_wide_lossy(description);
+        Self { extension, description }
+    }
+
+    /// Like [`FileTypeFilterWin32::new`], but fails if either string contains a NUL
+    pub fn try_new(extension: &str, description: &str) -> Result<Self, InteriorNulError> {
+        Self::try_with_extensions(&[extension], description)
+    }
+
+    /// Join extensions into a spec such as `*.png;*.jpg`, ignoring any leading `*.` or `.`
+    pub fn try_with_extensions<S>(extensions: &[S], description: &str) -> Result<Self, InteriorNulError> where S: AsRef<str> {
+        let spec = extensions.iter()
+            .map(|e| format!("*.{}", FileTypeFilter::normalize_extension(e.as_ref())))
+            .collect::<Vec<_>>().join(";");
+        Self::from_spec(&spec, description)
+    }
+
+    pub fn from_spec(spec: &str, description: &str) -> Result<Self, InteriorNulError> {
+        let extension = wide::to_wide(spec)?;
+        let description = wide::to_wide(description)?;
+        Ok(Self { extension, description })
+    }
+
+    pub fn get_extension(&self) -> PCWSTR { PCWSTR(self.extension.as_ptr()) }
+    pub fn get_description(&self) -> PCWSTR { PCWSTR(self.description.as_ptr()) }
+}
+
+impl Debug for FileTypeFilterWin32 {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileTypeFilterWin32")
+            .field("extension", &wide::from_wide(&self.extension))
+            .field("description", &wide::from_wide(&self.description))
+            .finish()
+    }
+}
+
+/// The forms a filter can be written as in a filter definition file: either just the extension,
+/// or an object with a description and list of extensions.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum FileTypeFilterDef {
+    Extension(String),
+    Full {
+        description: String,
+        extensions: Vec<String>
+    },
+    Spec {
+        description: String,
+        spec: String
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<FileTypeFilterDef> for FileTypeFilter {
+    type Error = String;
+    fn try_from(value: FileTypeFilterDef) -> Result<Self, Self::Error> {
+        match value {
+            FileTypeFilterDef::Extension(ext) => {
+                let description = format!("{} files", Self::normalize_extension(&ext).to_uppercase());
+                Self::try_new(ext, description)
+            },
+            FileTypeFilterDef::Full { description, extensions } => match extensions.is_empty() {
+                true => return Err(format!("filter \"{}\" has no extensions", description)),
+                false => Self::try_with_extensions(extensions, description)
+            },
+            FileTypeFilterDef::Spec { description, spec } => return Ok(Self::from_spec(spec, description))
+        }.map_err(|e| e.to_string())
+    }
+}
+
+/// Filters are always written in the full form so that the description survives a round trip.
+#[cfg(feature = "serde")]
+impl From<FileTypeFilter> for FileTypeFilterDef {
+    fn from(value: FileTypeFilter) -> Self {
+        match value.raw {
+            true => Self::Spec { description: value.description.clone(), spec: value.get_spec() },
+            false => Self::Full {
+                extensions: value.extensions().map(str::to_owned).collect(),
+                description: value.description
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub enum FilterLoadError {
+    Io(std::io::Error),
+    /// The file isn't a JSON array
+    Json(serde_json::Error),
+    /// An entry in the array isn't a valid filter
+    Entry {
+        index: usize,
+        source: serde_json::Error
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Display for FilterLoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "Couldn't read filter file: {}", e),
+            Self::Json(e) => write!(f, "Filter file isn't a list of filters: {}", e),
+            Self::Entry { index, source } => write!(f, "Filter {} is invalid: {}", index, source)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Error for FilterLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Entry { source, .. } => Some(source)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl FileTypeFilter {
+    /// Load a list of filters from a JSON file, such as:
+    /// ```json
+    /// [ "png", { "description": "DirectDraw Surface", "extensions": ["dds"] } ]
+    /// ```
+    pub fn load_set<P>(path: P) -> Result<Vec<FileTypeFilter>, FilterLoadError> where P: AsRef<Path> {
+        let text = std::fs::read_to_string(path).map_err(FilterLoadError::Io)?;
+        let entries: Vec<serde_json::Value> = serde_json::from_str(&text).map_err(FilterLoadError::Json)?;
+        entries.into_iter().enumerate().map(|(index, v)| {
+            serde_json::from_value(v).map_err(|source| FilterLoadError::Entry { index, source })
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_display() {
+        let filter = FileTypeFilter::new("p5path".to_owned(), "P5R Freecam Path".to_owned());
+        assert_eq!(filter.to_string(), "P5R Freecam Path (*.p5path)");
+        let filter = FileTypeFilter::from_spec("save_??.dat".to_owned(), "Save slots".to_owned());
+        assert_eq!(filter.to_string(), "Save slots (save_??.dat)");
+        assert_eq!(FileTypeFilter::for_extension("png").to_string(), "PNG images (*.png)");
+    }
+
+    #[test]
+    fn win32_filter_debug() {
+        let filter = FileTypeFilterWin32::from_spec("*.gmd", "GMD Model").unwrap();
+        assert_eq!(format!("{:?}", filter), r#"FileTypeFilterWin32 { extension: "*.gmd", description: "GMD Model" }"#);
+    }
+
+    fn spec(extension: &str) -> String {
+        FileTypeFilter::new(extension.to_owned(), String::new()).get_spec()
+    }
+
+    #[test]
+    fn leading_dot_and_wildcard_are_ignored() {
+        assert_eq!(spec("png"), "*.png");
+        assert_eq!(spec(".png"), "*.png");
+        assert_eq!(spec("*.png"), "*.png");
+        assert_eq!(spec(" *.png "), "*.png");
+        let filter = FileTypeFilterWin32::new(".png", "PNG images");
+        assert_eq!(format!("{:?}", filter), r#"FileTypeFilterWin32 { extension: "*.png", description: "PNG images" }"#);
+        let filter = FileTypeFilterWin32::new("*.png", "PNG images");
+        assert_eq!(format!("{:?}", filter), r#"FileTypeFilterWin32 { extension: "*.png", description: "PNG images" }"#);
+    }
+
+    #[test]
+    fn casing_is_kept_for_display() {
+        let filter = FileTypeFilter::new("*.PNG".to_owned(), "PNG images".to_owned());
+        assert_eq!(filter.get_extension(), "PNG");
+        assert_eq!(filter.get_spec(), "*.PNG");
+        assert!(filter.matches_extension(".png"));
+    }
+
+    #[test]
+    fn several_extensions_are_joined() {
+        let filter = FileTypeFilter::with_extensions(["png", ".jpg", "*.bmp"], "Image files".to_owned());
+        assert_eq!(filter.get_spec(), "*.png;*.jpg;*.bmp");
+        assert_eq!(filter.get_extensions(), vec!["png", "jpg", "bmp"]);
+        let filter = FileTypeFilterWin32::try_with_extensions(&["png", ".jpg"], "Image files").unwrap();
+        assert_eq!(format!("{:?}", filter), r#"FileTypeFilterWin32 { extension: "*.png;*.jpg", description: "Image files" }"#);
+    }
+
+    #[test]
+    fn all_files_spec() {
+        let filter = FileTypeFilter::all_files();
+        assert_eq!(filter.get_spec(), "*.*");
+        assert_eq!(filter.to_string(), "All files (*.*)");
+        assert!(filter.is_raw_spec());
+    }
+
+    #[test]
+    fn raw_spec_is_unchanged() {
+        assert_eq!(FileTypeFilter::from_spec("*.tar.gz".to_owned(), String::new()).get_spec(), "*.tar.gz");
+    }
+
+    #[test]
+    fn invalid_extensions_are_rejected() {
+        let try_new = |e: &str| FileTypeFilter::try_new(e.to_owned(), String::new());
+        assert_eq!(try_new("").unwrap_err(), FilterError::EmptyExtension);
+        assert_eq!(try_new("*.").unwrap_err(), FilterError::EmptyExtension);
+        assert_eq!(try_new("p*g").unwrap_err(), FilterError::InvalidCharacter { extension: "p*g".to_owned(), character: '*' });
+        assert_eq!(try_new("a/b").unwrap_err(), FilterError::InvalidCharacter { extension: "a/b".to_owned(), character: '/' });
+        assert!(try_new("*.png").is_ok());
+        assert_eq!(FileTypeFilter::try_with_extensions(Vec::<&str>::new(), String::new()).unwrap_err(), FilterError::EmptyExtension);
+        assert!(!FileTypeFilter::is_valid_extension("p?g"));
+        assert!(FileTypeFilter::is_valid_extension(".png"));
+    }
+
+    #[test]
+    fn interior_nul_is_rejected() {
+        assert_eq!(FileTypeFilterWin32::try_new("png\0", "PNG").unwrap_err(), InteriorNulError { position: 5 });
+        assert_eq!(FileTypeFilterWin32::from_spec("*.png", "P\0NG").unwrap_err(), InteriorNulError { position: 1 });
+        // the infallible constructor stops at the NUL instead
+        let filter = FileTypeFilterWin32::new("png\0jpg", "PNG");
+        assert_eq!(format!("{:?}", filter), r#"FileTypeFilterWin32 { extension: "*.png", description: "PNG" }"#);
+    }
+
+    fn hash_of(filter: &FileTypeFilter) -> u64 {
+        use std::hash::DefaultHasher;
+        let mut state = DefaultHasher::new();
+        filter.hash(&mut state);
+        state.finish()
+    }
+
+    #[test]
+    fn equal_filters_hash_the_same() {
+        let pairs = [
+            (FileTypeFilter::new("PNG".to_owned(), "Images".to_owned()), FileTypeFilter::new("png".to_owned(), "Images".to_owned())),
+            (FileTypeFilter::new("*.png".to_owned(), "Images".to_owned()), FileTypeFilter::new(".PNG".to_owned(), "Images".to_owned())),
+            (FileTypeFilter::with_extensions(["png", "JPG"], "Images".to_owned()), FileTypeFilter::with_extensions([".PNG", "*.jpg"], "Images".to_owned()))
+        ];
+        for (a, b) in pairs {
+            assert_eq!(a, b);
+            assert_eq!(hash_of(&a), hash_of(&b), "{} and {}", a, b);
+        }
+    }
+
+    #[test]
+    fn different_filters_are_not_equal() {
+        let png = FileTypeFilter::new("png".to_owned(), "Images".to_owned());
+        assert_ne!(png, FileTypeFilter::new("png".to_owned(), "PNG images".to_owned()));
+        assert_ne!(png, FileTypeFilter::from_spec("*.png".to_owned(), "Images".to_owned()));
+        assert_ne!(FileTypeFilter::with_extensions(["png", "jpg"], "Images".to_owned()), FileTypeFilter::with_extensions(["jpg", "png"], "Images".to_owned()));
+    }
+
+    #[test]
+    fn extension_case_is_deduplicated() {
+        let filters: std::collections::HashSet<FileTypeFilter> = [
+            FileTypeFilter::new("PNG".to_owned(), "Images".to_owned()),
+            FileTypeFilter::new("png".to_owned(), "Images".to_owned()),
+            FileTypeFilter::new("dds".to_owned(), "Images".to_owned())
+        ].into_iter().collect();
+        assert_eq!(filters.len(), 2);
+    }
+}
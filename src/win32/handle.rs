@@ -0,0 +1,135 @@
+use std::{
+    fs::{ File, OpenOptions },
+    io,
+    os::windows::fs::OpenOptionsExt,
+    path::{ Path, PathBuf }
+};
+use windows::Win32::{
+    Foundation::{ ERROR_LOCK_VIOLATION, ERROR_SHARING_VIOLATION },
+    Storage::FileSystem::{ FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE }
+};
+use super::{ DialogError, FileTypeFilter, OpenDialog, SaveDialog };
+
+/// What other handles to a file are allowed to do while it's open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShareMode {
+    pub read: bool,
+    pub write: bool,
+    pub delete: bool
+}
+
+impl ShareMode {
+    /// No other handles can be opened until this one is closed
+    pub const EXCLUSIVE: Self = Self { read: false, write: false, delete: false };
+    pub const READ: Self = Self { read: true, write: false, delete: false };
+    /// The same sharing that `std::fs::File` uses by default
+    pub const ALL: Self = Self { read: true, write: true, delete: true };
+
+    pub(crate) fn bits(&self) -> u32 {
+        let mut out = 0;
+        if self.read { out |= FILE_SHARE_READ.0 }
+        if self.write { out |= FILE_SHARE_WRITE.0 }
+        if self.delete { out |= FILE_SHARE_DELETE.0 }
+        out
+    }
+}
+
+impl Default for ShareMode {
+    fn default() -> Self { Self::READ }
+}
+
+/// How [`OpenDialog::open_file_handle`] opens the selected file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenAccess {
+    pub read: bool,
+    pub write: bool,
+    pub share: ShareMode
+}
+
+impl OpenAccess {
+    /// Read only, letting others read but not change or delete the file while it's open
+    pub const READ: Self = Self { read: true, write: false, share: ShareMode::READ };
+    pub const READ_WRITE: Self = Self { read: true, write: true, share: ShareMode::EXCLUSIVE };
+
+    fn open(&self, path: &Path) -> io::Result<File> {
+        OpenOptions::new()
+            .read(self.read)
+            .write(self.write)
+            .share_mode(self.share.bits())
+            .open(path)
+    }
+}
+
+impl Default for OpenAccess {
+    fn default() -> Self { Self::READ }
+}
+
+/// How [`SaveDialog::save_file_handle`] creates the selected file, matching `CreateFileW`'s
+/// creation dispositions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CreateDisposition {
+    /// Fail if the file already exists
+    CreateNew,
+    /// Create the file, truncating it if it already exists
+    #[default]
+    CreateAlways,
+    /// Open the file if it exists without truncating it, otherwise create it
+    OpenAlways,
+    /// Truncate the file, failing if it doesn't exist
+    TruncateExisting
+}
+
+impl CreateDisposition {
+    fn create(&self, path: &Path, share: ShareMode) -> io::Result<File> {
+        let mut options = OpenOptions::new();
+        options.write(true).share_mode(share.bits());
+        match self {
+            Self::CreateNew => options.create_new(true),
+            Self::CreateAlways => options.create(true).truncate(true),
+            Self::OpenAlways => options.create(true).truncate(false),
+            Self::TruncateExisting => options.truncate(true)
+        };
+        options.open(path)
+    }
+}
+
+/// Convert an error from opening a selected file, calling out files that are locked by another
+/// process since that's the most common reason for a file that was just selected to fail
+pub(crate) fn open_error(path: PathBuf, source: io::Error) -> DialogError {
+    let in_use = [ERROR_SHARING_VIOLATION.0 as i32, ERROR_LOCK_VIOLATION.0 as i32];
+    match source.raw_os_error() {
+        Some(code) if in_use.contains(&code) => DialogError::FileInUse { path, source },
+        _ => DialogError::OpenFailed { path, source }
+    }
+}
+
+impl<'a> OpenDialog<'a> {
+    /// Open a file and immediately open the selection, so that it can't be deleted, renamed or
+    /// locked between the dialog closing and the file being read
+    pub fn open_file_handle(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>, access: OpenAccess)
+        -> Result<Option<(PathBuf, File)>, DialogError> {
+        let Some(path) = self.open(filter, title)? else {
+            return Ok(None);
+        };
+        match access.open(&path) {
+            Ok(file) => Ok(Some((path, file))),
+            Err(e) => Err(open_error(path, e))
+        }
+    }
+}
+
+impl<'a> SaveDialog<'a> {
+    /// Save a file and immediately create the selection, so that another process can't take the
+    /// path between the dialog closing and the file being written. The file is created in a
+    /// single call, so a failure never leaves a partially created file behind.
+    pub fn save_file_handle(&mut self, filter: Option<&[FileTypeFilter]>, title: Option<&str>, create: CreateDisposition, share: ShareMode)
+        -> Result<Option<(PathBuf, File)>, DialogError> {
+        let Some(path) = self.save(filter, title)? else {
+            return Ok(None);
+        };
+        match create.create(&path, share) {
+            Ok(file) => Ok(Some((path, file))),
+            Err(e) => Err(open_error(path, e))
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::VecDeque,
+    path::{ Path, PathBuf }
+};
+
+/// Folders that files were recently picked from, along with folders that the user has pinned.
+/// Pinned folders are never evicted, while recent folders are dropped least recently used first
+/// once there's more than the limit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct FolderHistory {
+    pinned: Vec<PathBuf>,
+    // most recently used first
+    recent: VecDeque<PathBuf>,
+    limit: usize,
+    // number of folders to add to the dialog's navigation pane
+    places: usize
+}
+
+impl Default for FolderHistory {
+    fn default() -> Self {
+        Self { pinned: vec![], recent: VecDeque::new(), limit: 10, places: 0 }
+    }
+}
+
+impl FolderHistory {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn get_pinned(&self) -> &[PathBuf] { &self.pinned }
+    pub fn get_recent(&self) -> &VecDeque<PathBuf> { &self.recent }
+    pub fn get_limit(&self) -> usize { self.limit }
+    pub fn get_places(&self) -> usize { self.places }
+
+    /// Set how many unpinned folders are remembered
+    pub fn set_limit(&mut self, value: usize) {
+        self.limit = value;
+        self.recent.truncate(value);
+    }
+
+    /// Set how many pinned and recent folders are added to each dialog's navigation pane
+    pub fn set_places(&mut self, value: usize) { self.places = value }
+
+    /// Mark a folder as the most recently used one
+    pub fn record<P>(&mut self, folder: P) where P: AsRef<Path> {
+        let folder = folder.as_ref();
+        if self.is_pinned(folder) {
+            return;
+        }
+        self.recent.retain(|v| v != folder);
+        self.recent.push_front(folder.to_owned());
+        self.recent.truncate(self.limit);
+    }
+
+    pub fn is_pinned<P>(&self, folder: P) -> bool where P: AsRef<Path> {
+        self.pinned.iter().any(|v| v == folder.as_ref())
+    }
+
+    pub fn pin<P>(&mut self, folder: P) where P: AsRef<Path> {
+        let folder = folder.as_ref();
+        self.recent.retain(|v| v != folder);
+        if !self.is_pinned(folder) {
+            self.pinned.push(folder.to_owned());
+        }
+    }
+
+    /// Returns false if the folder wasn't pinned
+    pub fn unpin<P>(&mut self, folder: P) -> bool where P: AsRef<Path> {
+        let len = self.pinned.len();
+        self.pinned.retain(|v| v != folder.as_ref());
+        self.pinned.len() != len
+    }
+
+    /// Forget all recent folders, keeping pinned ones
+    pub fn clear_recent(&mut self) { self.recent.clear() }
+
+    /// Forget all recent and pinned folders
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.pinned.clear();
+    }
+
+    /// Folders to show in the navigation pane: pinned folders first, then recent ones, skipping
+    /// any that don't exist anymore.
+    pub fn get_place_folders(&self) -> Vec<PathBuf> {
+        self.pinned.iter().chain(self.recent.iter())
+            .filter(|v| v.is_dir())
+            .take(self.places)
+            .cloned()
+            .collect()
+    }
+
+    /// Folders recorded since `before` was copied from this history, oldest first. Recording
+    /// moves a folder to the front, so these are the front of the recent list up to where the
+    /// rest is still in the order that `before` had it.
+    pub(crate) fn recorded_since(&self, before: &Self) -> Vec<PathBuf> {
+        let recent: Vec<&PathBuf> = self.recent.iter().collect();
+        let count = (0..=recent.len()).find(|&count| {
+            let (moved, rest) = recent.split_at(count);
+            // folders that were pinned since don't count as moved
+            let mut older = before.recent.iter().filter(|v| !moved.contains(v) && self.recent.contains(v));
+            rest.iter().all(|v| older.next() == Some(*v))
+        }).unwrap_or(recent.len());
+        recent[..count].iter().rev().map(|v| (*v).clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recent(history: &FolderHistory) -> Vec<&str> {
+        history.get_recent().iter().map(|v| v.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn recent_is_most_recent_first() {
+        let mut history = FolderHistory::new();
+        for folder in ["a", "b", "c", "a"] {
+            history.record(folder);
+        }
+        assert_eq!(recent(&history), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn least_recent_is_evicted_at_limit() {
+        let mut history = FolderHistory::new();
+        history.set_limit(3);
+        for folder in ["a", "b", "c", "d"] {
+            history.record(folder);
+        }
+        assert_eq!(recent(&history), ["d", "c", "b"]);
+        // using a folder again keeps it over older ones
+        history.record("b");
+        history.record("e");
+        assert_eq!(recent(&history), ["e", "b", "d"]);
+    }
+
+    #[test]
+    fn lowering_the_limit_drops_oldest() {
+        let mut history = FolderHistory::new();
+        for folder in ["a", "b", "c"] {
+            history.record(folder);
+        }
+        history.set_limit(1);
+        assert_eq!(recent(&history), ["c"]);
+        history.set_limit(0);
+        history.record("d");
+        assert!(history.get_recent().is_empty());
+    }
+
+    #[test]
+    fn pinned_folders_are_not_recorded() {
+        let mut history = FolderHistory::new();
+        history.set_limit(2);
+        history.record("a");
+        history.pin("a");
+        // pinning moves the folder out of the recent list
+        assert!(history.get_recent().is_empty());
+        history.record("a");
+        history.record("b");
+        history.record("c");
+        assert_eq!(recent(&history), ["c", "b"]);
+        assert_eq!(history.get_pinned(), [PathBuf::from("a")]);
+    }
+
+    #[test]
+    fn pin_and_unpin() {
+        let mut history = FolderHistory::new();
+        history.pin("a");
+        history.pin("a");
+        history.pin("b");
+        assert_eq!(history.get_pinned(), [PathBuf::from("a"), PathBuf::from("b")]);
+        assert!(history.is_pinned("a"));
+        assert!(history.unpin("a"));
+        assert!(!history.unpin("a"));
+        assert!(!history.is_pinned("a"));
+        // an unpinned folder can be recorded again
+        history.record("a");
+        assert_eq!(recent(&history), ["a"]);
+        history.clear_recent();
+        assert!(history.get_recent().is_empty());
+        assert_eq!(history.get_pinned(), [PathBuf::from("b")]);
+        history.clear();
+        assert!(history.get_pinned().is_empty());
+    }
+
+    #[test]
+    fn place_folders_skip_missing() {
+        let root = std::env::temp_dir().join("riri-file-dialog-history");
+        let _ = std::fs::remove_dir_all(&root);
+        let [pinned, first, second] = ["pinned", "first", "second"].map(|name| {
+            let folder = root.join(name);
+            std::fs::create_dir_all(&folder).unwrap();
+            folder
+        });
+        let mut history = FolderHistory::new();
+        history.set_places(3);
+        history.pin(&pinned);
+        history.pin(root.join("gone"));
+        history.record(&second);
+        history.record(root.join("also gone"));
+        history.record(&first);
+        assert_eq!(history.get_place_folders(), [pinned.clone(), first.clone(), second]);
+        history.set_places(2);
+        assert_eq!(history.get_place_folders(), [pinned, first]);
+        history.set_places(0);
+        assert!(history.get_place_folders().is_empty());
+    }
+
+    #[test]
+    fn folders_recorded_since_copy() {
+        let mut before = FolderHistory::new();
+        for folder in ["a", "b", "c"] {
+            before.record(folder);
+        }
+        let mut history = before.clone();
+        assert!(history.recorded_since(&before).is_empty());
+        history.record("d");
+        history.record("a");
+        assert_eq!(history.recorded_since(&before), [PathBuf::from("d"), PathBuf::from("a")]);
+        // pinning takes a folder out of the list without recording anything
+        let mut history = before.clone();
+        history.pin("b");
+        assert!(history.recorded_since(&before).is_empty());
+        history.record("e");
+        assert_eq!(history.recorded_since(&before), [PathBuf::from("e")]);
+    }
+}
@@ -0,0 +1,65 @@
+/// Define a function that shows a dialog with a fixed configuration, so each dialog in an
+/// application is written once and called by name:
+///
+/// ```ignore
+/// define_dialog!(pub import_model: open { title: "Import model", filters: [("GMD Model", "gmd")], context: "models" });
+/// define_dialog!(export_model: save { title: "Export model", filters: [("GMD Model", "gmd")] });
+/// define_dialog!(pick_mod_folder: folder { title: "Choose a mod folder" });
+///
+/// let path = import_model(&mut manager)?;
+/// ```
+///
+/// The functions take the manager and return the selected path, `None` if the dialog was
+/// cancelled, or a [`DialogError`](crate::dialog::DialogError). Extensions are checked when
+/// compiling, with the same rules as [`FileTypeFilter::try_new`](crate::dialog::FileTypeFilter::try_new).
+/// A `context` starts the dialog in the folder remembered for that context, as with
+/// [`OpenDialog::context`](crate::dialog::OpenDialog::context).
+///
+/// Keys are given in the order `title`, `filters`, `context`. Unknown keys and keys given twice
+/// aren't accepted, tests/macro_ui has the errors these give.
+#[macro_export]
+macro_rules! define_dialog {
+    ($vis:vis $name:ident : open { title: $title:expr $(, filters: [$(($desc:literal, $ext:literal)),* $(,)?])? $(, context: $context:expr)? $(,)? }) => {
+        $vis fn $name(manager: &mut $crate::dialog::FileDialogManager)
+        -> ::std::result::Result<::std::option::Option<::std::path::PathBuf>, $crate::dialog::DialogError> {
+            let filters = $crate::define_dialog!(@filters $($(($desc, $ext)),*)?);
+            $crate::dialog::OpenDialog::create(manager)?$(.context($context))?.open((!filters.is_empty()).then_some(&filters[..]), Some($title))
+        }
+    };
+    ($vis:vis $name:ident : save { title: $title:expr $(, filters: [$(($desc:literal, $ext:literal)),* $(,)?])? $(, context: $context:expr)? $(,)? }) => {
+        $vis fn $name(manager: &mut $crate::dialog::FileDialogManager)
+        -> ::std::result::Result<::std::option::Option<::std::path::PathBuf>, $crate::dialog::DialogError> {
+            let filters = $crate::define_dialog!(@filters $($(($desc, $ext)),*)?);
+            $crate::dialog::SaveDialog::create(manager)?$(.context($context))?.save((!filters.is_empty()).then_some(&filters[..]), Some($title))
+        }
+    };
+    ($vis:vis $name:ident : folder { title: $title:expr $(, context: $context:expr)? $(,)? }) => {
+        $vis fn $name(manager: &mut $crate::dialog::FileDialogManager)
+        -> ::std::result::Result<::std::option::Option<::std::path::PathBuf>, $crate::dialog::DialogError> {
+            $crate::dialog::OpenDialog::create(manager)?$(.context($context))?.open_folder(Some($title))
+        }
+    };
+    (@filters $(($desc:literal, $ext:literal)),*) => {{
+        $(const _: () = assert!($crate::dialog::FileTypeFilter::is_valid_extension($ext),
+            concat!("\"", $ext, "\" isn't a valid extension for a file type filter"));)*
+        ::std::vec![$($crate::dialog::FileTypeFilter::new($ext.to_owned(), $desc.to_owned())),*] as ::std::vec::Vec<$crate::dialog::FileTypeFilter>
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::dialog::{ DialogError, FileDialogManager };
+
+    crate::define_dialog!(import_model: open { title: "Import model", filters: [("GMD Model", "gmd")], context: "models" });
+    crate::define_dialog!(export_model: save { title: "Export model", filters: [("GMD Model", "gmd"), ("GFS Model", "*.gfs"),] });
+    crate::define_dialog!(pick_mod_folder: folder { title: "Choose a mod folder", context: "mods", });
+
+    type Dialog = fn(&mut FileDialogManager) -> Result<Option<PathBuf>, DialogError>;
+
+    #[test]
+    fn functions_return_dialog_errors() {
+        let dialogs: [Dialog; 3] = [import_model, export_model, pick_mod_folder];
+        assert_eq!(dialogs.len(), 3);
+    }
+}
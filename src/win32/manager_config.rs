@@ -0,0 +1,204 @@
+use std::path::PathBuf;
+use windows::{ core::GUID, Win32::Foundation::HWND };
+use crate::options::DialogOptions;
+use super::{
+    CompletionNotification,
+    FileDialogManager,
+    FolderHistory,
+    MonitorSelection,
+    OwnerPolicy,
+    OwnerSource
+};
+
+/// Everything a [`FileDialogManager`] can be created with. Settings that aren't given keep the same
+/// defaults as [`FileDialogManager::new`]:
+///
+/// ```ignore
+/// FileDialogManager::init(ManagerConfig::new()
+///     .default_open(game_folder)
+///     .default_save(workspace)
+///     .owner(hwnd)
+///     .confirm_overwrite(true));
+/// ```
+///
+/// With the `serde` feature, this can be loaded from a settings file. The owner window isn't
+/// serialized since window handles don't mean anything to another process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ManagerConfig {
+    pub default_open: PathBuf,
+    pub default_save: PathBuf,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub owner: Option<HWND>,
+    pub owner_policy: OwnerPolicy,
+    pub target_monitor: Option<MonitorSelection>,
+    pub dedup_filters: bool,
+    pub confirm_overwrite: bool,
+    pub completion_notification: CompletionNotification,
+    pub default_options: DialogOptions,
+    pub history: FolderHistory,
+    #[cfg_attr(feature = "serde", serde(with = "guid"))]
+    pub client_guid: Option<GUID>
+}
+
+impl Default for ManagerConfig {
+    fn default() -> Self {
+        Self {
+            default_open: PathBuf::new(),
+            default_save: PathBuf::new(),
+            owner: None,
+            owner_policy: OwnerPolicy::default(),
+            target_monitor: None,
+            dedup_filters: true,
+            confirm_overwrite: false,
+            completion_notification: CompletionNotification::default(),
+            default_options: DialogOptions::new(),
+            history: FolderHistory::default(),
+            client_guid: None
+        }
+    }
+}
+
+impl ManagerConfig {
+    pub fn new() -> Self { Self::default() }
+
+    /// Use the same default path for open and save dialogs
+    pub fn default_path(self, value: PathBuf) -> Self {
+        self.default_open(value.clone()).default_save(value)
+    }
+    pub fn default_open(mut self, value: PathBuf) -> Self { self.default_open = value; self }
+    pub fn default_save(mut self, value: PathBuf) -> Self { self.default_save = value; self }
+    /// Without an owner, the manager behaves like one made with [`FileDialogManager::new_deferred`]
+    pub fn owner(mut self, value: HWND) -> Self { self.owner = Some(value); self }
+    pub fn owner_policy(mut self, value: OwnerPolicy) -> Self { self.owner_policy = value; self }
+    pub fn target_monitor(mut self, value: MonitorSelection) -> Self { self.target_monitor = Some(value); self }
+    pub fn dedup_filters(mut self, value: bool) -> Self { self.dedup_filters = value; self }
+    pub fn confirm_overwrite(mut self, value: bool) -> Self { self.confirm_overwrite = value; self }
+    pub fn completion_notification(mut self, value: CompletionNotification) -> Self { self.completion_notification = value; self }
+    pub fn default_options(mut self, value: DialogOptions) -> Self { self.default_options = value; self }
+    pub fn history(mut self, value: FolderHistory) -> Self { self.history = value; self }
+    pub fn client_guid(mut self, value: GUID) -> Self { self.client_guid = Some(value); self }
+
+    /// Create a manager that isn't installed as the global one, such as for
+    /// [`FileDialogManager::register_window`]-style use. See [`FileDialogManager::init`].
+    pub fn build(self) -> FileDialogManager {
+        let mut out = FileDialogManager::create(self.default_open, self.owner.map(OwnerSource::Window));
+        out.initial_save = self.default_save.clone();
+        out.default_save = self.default_save;
+        out.owner_policy = self.owner_policy;
+        out.target_monitor = self.target_monitor;
+        out.dedup_filters = self.dedup_filters;
+        out.confirm_overwrite = self.confirm_overwrite;
+        out.completion_notification = self.completion_notification;
+        out.default_options = self.default_options;
+        out.history = self.history;
+        out.set_client_guid(self.client_guid);
+        out
+    }
+}
+
+impl FileDialogManager {
+    /// The manager's current settings, for checking what it was set up with. An owner set with a
+    /// callback or auto-detection is left out, since it can't be represented by a window handle.
+    pub fn get_config(&self) -> ManagerConfig {
+        ManagerConfig {
+            default_open: self.default_open.clone(),
+            default_save: self.default_save.clone(),
+            owner: match self.owner.as_deref() {
+                Some(OwnerSource::Window(hwnd)) => Some(*hwnd),
+                _ => None
+            },
+            owner_policy: self.owner_policy,
+            target_monitor: self.target_monitor,
+            dedup_filters: self.dedup_filters,
+            confirm_overwrite: self.confirm_overwrite,
+            completion_notification: self.completion_notification,
+            default_options: self.default_options,
+            history: self.history.clone(),
+            client_guid: self.client_guid
+        }
+    }
+}
+
+// GUIDs are stored as their 128-bit value
+#[cfg(feature = "serde")]
+mod guid {
+    use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+    use windows::core::GUID;
+
+    pub fn serialize<S>(value: &Option<GUID>, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        value.map(|g| g.to_u128()).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<GUID>, D::Error> where D: Deserializer<'de> {
+        Ok(Option::<u128>::deserialize(deserializer)?.map(GUID::from_u128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PostedMessage;
+    use super::*;
+
+    fn full_config() -> ManagerConfig {
+        let mut history = FolderHistory::new();
+        history.set_limit(4);
+        history.record(r"C:\mods\recent");
+        history.pin(r"C:\mods");
+        ManagerConfig::new()
+            .default_open(PathBuf::from(r"C:\games\p5r"))
+            .default_save(PathBuf::from(r"C:\workspace"))
+            .owner(HWND(0x1001 as _))
+            .owner_policy(OwnerPolicy::Unowned)
+            .target_monitor(MonitorSelection::Index(1))
+            .dedup_filters(false)
+            .confirm_overwrite(true)
+            .completion_notification(CompletionNotification {
+                flash: true,
+                post_message: Some(PostedMessage { message: 0x8001, wparam: 2, lparam: -1 })
+            })
+            .default_options(DialogOptions::new().show_hidden(true).no_change_dir(false))
+            .history(history)
+            .client_guid(GUID::from_u128(0x1234_5678_9abc_def0_1122_3344_5566_7788))
+    }
+
+    #[test]
+    fn build_round_trip() {
+        let config = full_config();
+        assert_eq!(config.clone().build().get_config(), config);
+    }
+
+    #[test]
+    fn default_round_trip() {
+        assert_eq!(ManagerConfig::new().build().get_config(), ManagerConfig::default());
+    }
+
+    #[test]
+    fn default_path_sets_both() {
+        let config = ManagerConfig::new().default_path(PathBuf::from(r"C:\games"));
+        assert_eq!(config.default_open, PathBuf::from(r"C:\games"));
+        assert_eq!(config.default_save, PathBuf::from(r"C:\games"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let config = full_config();
+        let text = serde_json::to_string(&config).unwrap();
+        assert!(text.contains(&format!("\"client_guid\":{}", 0x1234_5678_9abc_def0_1122_3344_5566_7788u128)), "{}", text);
+        assert!(!text.contains("owner\""), "{}", text);
+        let loaded: ManagerConfig = serde_json::from_str(&text).unwrap();
+        // window handles aren't saved
+        assert_eq!(loaded, ManagerConfig { owner: None, ..config });
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_missing_fields_are_defaults() {
+        let loaded: ManagerConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(loaded, ManagerConfig::default());
+        let loaded: ManagerConfig = serde_json::from_str(r#"{ "client_guid": null, "confirm_overwrite": true }"#).unwrap();
+        assert_eq!(loaded, ManagerConfig::new().confirm_overwrite(true));
+    }
+}
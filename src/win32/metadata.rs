@@ -0,0 +1,39 @@
+use std::{
+    path::{ Path, PathBuf },
+    time::SystemTime
+};
+
+/// A selected path along with the filter that was selected in the dialog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DialogSelection {
+    pub path: PathBuf,
+    /// Index into the filters passed to the dialog, or `None` if there weren't any
+    pub filter_index: Option<usize>
+}
+
+/// Details about a selected file, collected after the dialog closes when enabled with
+/// `collect_metadata` on a dialog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelectionInfo {
+    pub path: PathBuf,
+    /// The file has the read-only attribute, which is common for files synced from cloud storage.
+    /// False for paths that don't exist yet.
+    pub read_only: bool,
+    /// Size in bytes, or `None` if the path doesn't exist yet
+    pub len: Option<u64>,
+    pub modified: Option<SystemTime>
+}
+
+impl SelectionInfo {
+    pub fn query(path: &Path) -> Self {
+        match std::fs::metadata(path) {
+            Ok(meta) => Self {
+                path: path.to_owned(),
+                read_only: meta.permissions().readonly(),
+                len: meta.is_file().then_some(meta.len()),
+                modified: meta.modified().ok()
+            },
+            Err(_) => Self { path: path.to_owned(), read_only: false, len: None, modified: None }
+        }
+    }
+}
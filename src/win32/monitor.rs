@@ -0,0 +1,93 @@
+use windows::{
+    core::BOOL,
+    Win32::{
+        Foundation::{ HWND, LPARAM, POINT, RECT },
+        Graphics::Gdi::{
+            EnumDisplayMonitors,
+            GetMonitorInfoW,
+            HDC,
+            HMONITOR,
+            MONITORINFO,
+            MONITOR_DEFAULTTONEAREST,
+            MONITOR_DEFAULTTOPRIMARY,
+            MonitorFromPoint,
+            MonitorFromWindow
+        },
+        UI::WindowsAndMessaging::{
+            GetCursorPos,
+            GetWindowRect,
+            SetWindowPos,
+            SWP_NOACTIVATE,
+            SWP_NOSIZE,
+            SWP_NOZORDER
+        }
+    }
+};
+
+/// Which monitor a dialog should be moved to once it's been shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MonitorSelection {
+    /// The primary display
+    Primary,
+    /// A monitor by its position in [`EnumDisplayMonitors`] order. Falls back to the owner's
+    /// monitor if there aren't that many monitors.
+    Index(usize),
+    /// The monitor that the owner window is mostly on
+    ContainingOwner,
+    /// The monitor that the mouse cursor is currently on
+    ContainingCursor
+}
+
+impl MonitorSelection {
+    pub(crate) fn find(&self, owner: Option<HWND>, dialog: HWND) -> HMONITOR {
+        match self {
+            Self::Primary => unsafe { MonitorFromPoint(POINT::default(), MONITOR_DEFAULTTOPRIMARY) },
+            Self::Index(i) => match enumerate_monitors().get(*i) {
+                Some(v) => *v,
+                None => {
+                    log::warn!("Monitor {} doesn't exist, using the owner window's monitor", i);
+                    Self::ContainingOwner.find(owner, dialog)
+                }
+            },
+            Self::ContainingOwner => unsafe { MonitorFromWindow(owner.unwrap_or(dialog), MONITOR_DEFAULTTONEAREST) },
+            Self::ContainingCursor => {
+                let mut cursor = POINT::default();
+                match unsafe { GetCursorPos(&mut cursor) } {
+                    Ok(_) => unsafe { MonitorFromPoint(cursor, MONITOR_DEFAULTTONEAREST) },
+                    Err(_) => Self::ContainingOwner.find(owner, dialog)
+                }
+            }
+        }
+    }
+
+    /// Center the dialog in the work area of the selected monitor
+    pub(crate) fn move_dialog(&self, owner: Option<HWND>, dialog: HWND) {
+        let monitor = self.find(owner, dialog);
+        let mut info = MONITORINFO { cbSize: size_of::<MONITORINFO>() as u32, ..Default::default() };
+        let mut rect = RECT::default();
+        if !unsafe { GetMonitorInfoW(monitor, &mut info) }.as_bool()
+        || unsafe { GetWindowRect(dialog, &mut rect) }.is_err() {
+            log::warn!("Couldn't get the layout of monitor {:?}, leaving dialog in place", monitor);
+            return;
+        }
+        let work = info.rcWork;
+        let x = work.left + ((work.right - work.left) - (rect.right - rect.left)) / 2;
+        let y = work.top + ((work.bottom - work.top) - (rect.bottom - rect.top)) / 2;
+        if let Err(e) = unsafe { SetWindowPos(dialog, None, x, y, 0, 0, SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE) } {
+            log::warn!("Couldn't move dialog to monitor {:?}: {}", monitor, e);
+        }
+    }
+}
+
+fn enumerate_monitors() -> Vec<HMONITOR> {
+    let mut monitors: Vec<HMONITOR> = vec![];
+    let _ = unsafe { EnumDisplayMonitors(None, None, Some(enumerate_monitors_proc), LPARAM(&raw mut monitors as isize)) };
+    monitors
+}
+
+unsafe extern "system" fn enumerate_monitors_proc(monitor: HMONITOR, _: HDC, _: *mut RECT, param: LPARAM) -> BOOL {
+    let monitors = unsafe { &mut *(param.0 as *mut Vec<HMONITOR>) };
+    monitors.push(monitor);
+    true.into()
+}
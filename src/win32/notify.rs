@@ -0,0 +1,61 @@
+use windows::Win32::{
+    Foundation::{ HWND, LPARAM, WPARAM },
+    UI::WindowsAndMessaging::{
+        FlashWindowEx,
+        FLASHWINFO,
+        FLASHW_ALL,
+        FLASHW_TIMERNOFG,
+        PostMessageW
+    }
+};
+
+/// A window message posted to the owner window when a dialog closes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PostedMessage {
+    pub message: u32,
+    pub wparam: usize,
+    pub lparam: isize
+}
+
+/// Ways of letting the user and host application know that a dialog has closed, for when the user
+/// switched back to the application while the dialog was open. These fire for every outcome,
+/// including cancelling.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompletionNotification {
+    /// Flash the owner window's taskbar button until it's brought to the foreground
+    pub flash: bool,
+    /// Post a message to the owner window, so the application can react (such as by unpausing)
+    pub post_message: Option<PostedMessage>
+}
+
+impl CompletionNotification {
+    pub fn is_empty(&self) -> bool {
+        !self.flash && self.post_message.is_none()
+    }
+
+    pub(crate) fn fire(&self, owner: Option<HWND>) {
+        let Some(owner) = owner else {
+            if !self.is_empty() {
+                log::debug!("Dialog has no owner window, skipping completion notification");
+            }
+            return;
+        };
+        if self.flash {
+            let info = FLASHWINFO {
+                cbSize: size_of::<FLASHWINFO>() as u32,
+                hwnd: owner,
+                dwFlags: FLASHW_ALL | FLASHW_TIMERNOFG,
+                uCount: 0,
+                dwTimeout: 0
+            };
+            // The return value is the previous flash state, not an error
+            let _ = unsafe { FlashWindowEx(&info) };
+        }
+        if let Some(msg) = self.post_message
+            && let Err(e) = unsafe { PostMessageW(Some(owner), msg.message, WPARAM(msg.wparam), LPARAM(msg.lparam)) } {
+            log::warn!("Couldn't post completion message to {:?}: {}", owner.0, e);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt::{ Display, Formatter };
+#[cfg(feature = "regex")]
+use super::{ Regex, RegexError };
+
+/// A pattern that selected file names must match. Matching ignores ASCII case, like Windows file
+/// names, and is applied to the file name only, not the folder.
+///
+/// Regular expressions need the `regex` feature.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NamePattern {
+    /// `*` matches any run of characters, `?` matches one character and `[...]` matches one of a
+    /// set of characters, such as `[0-9]` or `[!_]`
+    Glob(String),
+    /// A regular expression that has to match the whole name, such as `SLOT_\d{2}\.sav`. See
+    /// [`Regex`] for the supported syntax.
+    #[cfg(feature = "regex")]
+    Regex(Regex)
+}
+
+impl NamePattern {
+    pub fn glob<S>(pattern: S) -> Self where S: Into<String> {
+        Self::Glob(pattern.into())
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Result<Self, RegexError> {
+        Ok(Self::Regex(Regex::new(pattern)?))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => {
+                let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+                let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
+                glob_matches(&pattern, &name)
+            },
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => regex.is_match(name)
+        }
+    }
+}
+
+impl Display for NamePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Glob(pattern) => write!(f, "{}", pattern),
+            #[cfg(feature = "regex")]
+            Self::Regex(regex) => write!(f, "{}", regex)
+        }
+    }
+}
+
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // where to resume if the most recent * needs to match more characters
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+                continue;
+            },
+            Some('?') => {
+                p += 1;
+                n += 1;
+                continue;
+            },
+            Some('[') => if let Some((matched, len)) = match_class(&pattern[p..], name[n]) {
+                if matched {
+                    p += len;
+                    n += 1;
+                    continue;
+                }
+            } else if name[n] == '[' {
+                // no closing bracket, so treat it as a literal
+                p += 1;
+                n += 1;
+                continue;
+            },
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+                continue;
+            },
+            _ => ()
+        }
+        match star {
+            Some((sp, sn)) => {
+                p = sp + 1;
+                n = sn + 1;
+                star = Some((sp, sn + 1));
+            },
+            None => return false
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a character against a `[...]` class at the start of `pattern`, returning whether it
+/// matched and the length of the class, or `None` if the class isn't closed
+fn match_class(pattern: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = matches!(pattern.get(i), Some('!') | Some('^'));
+    if negate {
+        i += 1;
+    }
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let start = *pattern.get(i)?;
+        if start == ']' && !first {
+            return Some((matched != negate, i + 1));
+        }
+        first = false;
+        if pattern.get(i + 1) == Some(&'-') && let Some(end) = pattern.get(i + 2).filter(|e| **e != ']') {
+            matched |= (start..=*end).contains(&c);
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob(pattern: &str, name: &str) -> bool {
+        NamePattern::glob(pattern).matches(name)
+    }
+
+    #[test]
+    fn star_matches_any_run() {
+        assert!(glob("*", ""));
+        assert!(glob("*", "anything.sav"));
+        assert!(glob("*.sav", "slot.sav"));
+        assert!(glob("*.sav", ".sav"));
+        assert!(!glob("*.sav", "slot.sav.bak"));
+        assert!(glob("slot*.sav", "slot_01.sav"));
+        assert!(glob("*_*_*", "a_b_c"));
+        assert!(!glob("*_*_*", "a_b"));
+        assert!(glob("a**b", "ab"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(glob("slot_??.sav", "slot_01.sav"));
+        assert!(!glob("slot_??.sav", "slot_1.sav"));
+        assert!(!glob("slot_??.sav", "slot_001.sav"));
+        assert!(glob("?", "é"));
+        assert!(!glob("?", ""));
+    }
+
+    #[test]
+    fn class_matches_listed_characters() {
+        assert!(glob("slot_[abc].sav", "slot_b.sav"));
+        assert!(!glob("slot_[abc].sav", "slot_d.sav"));
+        // ] first in the class is a literal
+        assert!(glob("[]x]", "]"));
+    }
+
+    #[test]
+    fn class_ranges() {
+        assert!(glob("slot_[0-9][0-9].sav", "slot_42.sav"));
+        assert!(!glob("slot_[0-9][0-9].sav", "slot_4x.sav"));
+        assert!(glob("[a-cx-z]", "y"));
+        assert!(!glob("[a-cx-z]", "m"));
+        // - before the closing bracket is a literal
+        assert!(glob("[a-]", "-"));
+    }
+
+    #[test]
+    fn negated_class() {
+        assert!(glob("[!_]*", "save.sav"));
+        assert!(!glob("[!_]*", "_backup.sav"));
+        assert!(glob("[^0-9]", "a"));
+        assert!(!glob("[^0-9]", "5"));
+    }
+
+    #[test]
+    fn unclosed_class_is_literal() {
+        assert!(glob("slot[1", "slot[1"));
+        assert!(!glob("slot[1", "slot1"));
+        assert!(glob("*[", "name["));
+        assert!(!glob("[", ""));
+    }
+
+    #[test]
+    fn empty_pattern_and_name() {
+        assert!(glob("", ""));
+        assert!(!glob("", "a"));
+        assert!(!glob("a", ""));
+        assert!(glob("**", ""));
+    }
+
+    #[test]
+    fn ascii_case_is_ignored() {
+        assert!(glob("SLOT_*.SAV", "slot_01.sav"));
+        assert!(glob("slot_[a-c].sav", "SLOT_B.SAV"));
+        assert!(glob("[A-C]", "b"));
+        // only ASCII letters are folded
+        assert!(!glob("É", "é"));
+    }
+
+    #[test]
+    fn display_is_the_pattern() {
+        assert_eq!(NamePattern::glob("slot_??.sav").to_string(), "slot_??.sav");
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn regex_patterns() {
+        let slot = NamePattern::regex(r"SLOT_\d{2}\.sav").unwrap();
+        assert!(slot.matches("slot_07.SAV"));
+        assert!(!slot.matches("SLOT_7.sav"));
+        assert_eq!(slot.to_string(), r"SLOT_\d{2}\.sav");
+        assert_eq!(slot, NamePattern::regex(r"SLOT_\d{2}\.sav").unwrap());
+        assert!(NamePattern::regex("(").is_err());
+    }
+}
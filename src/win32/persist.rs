@@ -0,0 +1,259 @@
+use std::{
+    io,
+    path::{ Path, PathBuf }
+};
+use windows::Win32::Foundation::HWND;
+use super::{ FileDialogManager, OwnerSource };
+
+// Lines are `key=value`, with keys `open`, `save`, `open.<context>` and `save.<context>`. Values
+// are everything after the first `=`, so paths containing `=` are kept intact.
+const HEADER: &str = "# riri-file-dialog state";
+
+impl FileDialogManager {
+    /// Like [`FileDialogManager::new`], but remembered folders are loaded from `state_file` and
+    /// written back to it after each successful selection. If the file is missing or can't be
+    /// read, or an entry points at a folder that no longer exists, `default` is used instead.
+    #[allow(clippy::new_ret_no_self)]
+    pub fn with_persistence(default: PathBuf, window: HWND, state_file: PathBuf) {
+        let mut manager = Self::create(default, Some(OwnerSource::Window(window)));
+        manager.load_state(&state_file);
+        manager.state_file = Some(state_file);
+        Self::set_global(manager)
+    }
+
+    pub fn get_state_file(&self) -> Option<&Path> { self.state_file.as_deref() }
+    /// Save remembered folders to a file after each successful selection. This doesn't load the file.
+    pub fn set_state_file(&mut self, value: Option<PathBuf>) { self.state_file = value }
+
+    /// Write the remembered folders to the state file. Does nothing if the manager doesn't have one.
+    pub fn save_state(&self) -> io::Result<()> {
+        let Some(file) = self.state_file.as_ref() else {
+            return Ok(());
+        };
+        let mut entries = vec![("open".to_owned(), self.default_open.as_path()), ("save".to_owned(), self.default_save.as_path())];
+        entries.extend(self.open_contexts.iter().map(|(k, v)| (format!("open.{}", k), v.as_path())));
+        entries.extend(self.save_contexts.iter().map(|(k, v)| (format!("save.{}", k), v.as_path())));
+        let mut out = format!("{}\n", HEADER);
+        for (key, path) in entries {
+            if key.contains(['=', '\n', '\r']) {
+                log::warn!("Context {:?} can't be saved, it contains '=' or a line break", key);
+                continue;
+            }
+            // Storing a lossy copy would restore a different path
+            let Some(value) = path.to_str() else {
+                log::warn!("Not saving {}, {} isn't valid Unicode", key, path.display());
+                continue;
+            };
+            out.push_str(&format!("{}={}\n", key, value));
+        }
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file, out)
+    }
+
+    /// Save the state file if there is one, logging rather than returning errors since this runs
+    /// after a dialog has already succeeded
+    pub(crate) fn autosave_state(&self) {
+        if let Err(e) = self.save_state() {
+            log::warn!("Couldn't save dialog state: {}", e);
+        }
+    }
+
+    fn load_state(&mut self, file: &Path) {
+        let text = match std::fs::read_to_string(file) {
+            Ok(v) => v,
+            Err(e) => {
+                if e.kind() != io::ErrorKind::NotFound {
+                    log::warn!("Couldn't read dialog state from {}: {}", file.display(), e);
+                }
+                return;
+            }
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                log::warn!("Ignoring line {} of {}, it isn't key=value", number + 1, file.display());
+                continue;
+            };
+            let path = PathBuf::from(value);
+            if !Self::still_exists(&path) {
+                log::info!("Ignoring remembered path {}, its folder no longer exists", path.display());
+                continue;
+            }
+            match key.split_once('.') {
+                None if key == "open" => self.default_open = path,
+                None if key == "save" => self.default_save = path,
+                Some(("open", context)) => { self.open_contexts.insert(context.to_owned(), path); },
+                Some(("save", context)) => { self.save_contexts.insert(context.to_owned(), path); },
+                _ => log::warn!("Ignoring unknown key {:?} in {}", key, file.display())
+            }
+        }
+    }
+
+    // Remembered paths can be files or folders, so check the folder they're in
+    fn still_exists(path: &Path) -> bool {
+        path.is_dir() || path.parent().is_some_and(Path::is_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ ffi::OsString, os::windows::ffi::OsStringExt };
+    use super::super::ClearScope;
+    use super::*;
+
+    // An empty folder for one test, with a state file path inside it
+    fn temp_folder(name: &str) -> (PathBuf, PathBuf) {
+        let folder = std::env::temp_dir().join("riri-file-dialog-persist").join(name);
+        let _ = std::fs::remove_dir_all(&folder);
+        std::fs::create_dir_all(&folder).unwrap();
+        let state = folder.join("state.txt");
+        (folder, state)
+    }
+
+    fn subfolder(folder: &Path, name: &str) -> PathBuf {
+        let out = folder.join(name);
+        std::fs::create_dir_all(&out).unwrap();
+        out
+    }
+
+    fn saved_manager(default: PathBuf, state: &Path) -> FileDialogManager {
+        let mut manager = FileDialogManager::create(default, None);
+        manager.set_state_file(Some(state.to_owned()));
+        manager
+    }
+
+    fn loaded_manager(default: PathBuf, state: &Path) -> FileDialogManager {
+        let mut manager = FileDialogManager::create(default, None);
+        manager.load_state(state);
+        manager
+    }
+
+    #[test]
+    fn state_round_trip() {
+        let (folder, state) = temp_folder("round_trip");
+        let models = subfolder(&folder, "models");
+        let exports = subfolder(&folder, "exports");
+        let mut manager = saved_manager(folder.clone(), &state);
+        manager.set_default_open(models.join("cammy.gmd"));
+        manager.set_default_save(&exports);
+        manager.set_default_open_for("models", &models);
+        manager.set_default_save_for("exports", exports.join("level3.json"));
+        manager.save_state().unwrap();
+
+        let loaded = loaded_manager(std::env::temp_dir(), &state);
+        assert_eq!(loaded.default_open, models.join("cammy.gmd"));
+        assert_eq!(loaded.default_save, exports);
+        assert_eq!(loaded.open_contexts, manager.open_contexts);
+        assert_eq!(loaded.save_contexts, manager.save_contexts);
+    }
+
+    #[test]
+    fn non_ascii_paths() {
+        let (folder, state) = temp_folder("non_ascii");
+        let models = subfolder(&folder, "モデル");
+        let exports = subfolder(&folder, "Übersetzung – ß");
+        let mut manager = saved_manager(folder, &state);
+        manager.set_default_open(&models);
+        manager.set_default_save_for("exports", &exports);
+        manager.save_state().unwrap();
+
+        let loaded = loaded_manager(std::env::temp_dir(), &state);
+        assert_eq!(loaded.default_open, models);
+        assert_eq!(loaded.save_contexts.get("exports"), Some(&exports));
+    }
+
+    #[test]
+    fn paths_that_arent_unicode_are_skipped() {
+        let (folder, state) = temp_folder("not_unicode");
+        // an unpaired surrogate, which Windows allows in file names
+        let name = OsString::from_wide(&[0x61, 0xD800, 0x62]);
+        let mut manager = saved_manager(folder.clone(), &state);
+        manager.set_default_open_for("bad", folder.join(name));
+        manager.set_default_save_for("good", &folder);
+        manager.save_state().unwrap();
+
+        let loaded = loaded_manager(std::env::temp_dir(), &state);
+        assert!(loaded.open_contexts.is_empty());
+        assert_eq!(loaded.save_contexts.get("good"), Some(&folder));
+        assert_eq!(loaded.default_open, folder);
+    }
+
+    #[test]
+    fn missing_file_keeps_defaults() {
+        let (folder, state) = temp_folder("missing");
+        let loaded = loaded_manager(folder.clone(), &state);
+        assert_eq!(loaded.default_open, folder);
+        assert_eq!(loaded.default_save, folder);
+        assert!(loaded.open_contexts.is_empty());
+        assert!(loaded.save_contexts.is_empty());
+    }
+
+    #[test]
+    fn unreadable_file_keeps_defaults() {
+        let (folder, state) = temp_folder("unreadable");
+        // not UTF-8, so the file can't be read at all
+        std::fs::write(&state, [0xff, 0xfe, 0x00, 0x80, b'=', 0xc3]).unwrap();
+        let loaded = loaded_manager(folder.clone(), &state);
+        assert_eq!(loaded.default_open, folder);
+        assert_eq!(loaded.default_save, folder);
+    }
+
+    #[test]
+    fn bad_lines_are_ignored() {
+        let (folder, state) = temp_folder("bad_lines");
+        let models = subfolder(&folder, "models");
+        let gone = folder.join("gone").join("file.gmd");
+        std::fs::write(&state, format!("{}\nnot a key value line\nsize=12\nopen={}\nsave.exports={}\nsave={}\n",
+            HEADER, models.display(), gone.display(), models.display())).unwrap();
+        let loaded = loaded_manager(folder, &state);
+        assert_eq!(loaded.default_open, models);
+        assert_eq!(loaded.default_save, models);
+        assert!(loaded.save_contexts.is_empty());
+    }
+
+    #[test]
+    fn clearing_saves_initial_folders() {
+        let (folder, state) = temp_folder("clear");
+        let models = subfolder(&folder, "models");
+        let mut manager = saved_manager(folder.clone(), &state);
+        manager.set_default_open(&models);
+        manager.set_default_save_for("models", &models);
+        manager.save_state().unwrap();
+
+        let report = manager.clear_persisted_state(ClearScope::ALL);
+        assert!(matches!(report.state_file, Some(Ok(()))));
+        assert!(report.is_ok());
+        let loaded = loaded_manager(std::env::temp_dir(), &state);
+        assert_eq!(loaded.default_open, folder);
+        assert_eq!(loaded.default_save, folder);
+        assert!(loaded.save_contexts.is_empty());
+    }
+
+    #[test]
+    fn clearing_only_the_file_deletes_it() {
+        let (folder, state) = temp_folder("clear_file");
+        let models = subfolder(&folder, "models");
+        let mut manager = saved_manager(folder, &state);
+        manager.set_default_open(&models);
+        manager.save_state().unwrap();
+
+        let report = manager.clear_persisted_state(ClearScope::STATE_FILE);
+        assert!(matches!(report.state_file, Some(Ok(()))));
+        assert!(!state.exists());
+        assert_eq!(manager.default_open, models);
+        // a missing file isn't an error
+        assert!(matches!(manager.clear_persisted_state(ClearScope::STATE_FILE).state_file, Some(Ok(()))));
+    }
+
+    #[test]
+    fn clearing_without_a_file() {
+        let mut manager = FileDialogManager::create(std::env::temp_dir(), None);
+        let report = manager.clear_persisted_state(ClearScope { memory: true, client_data: false, state_file: true });
+        assert!(report.memory && report.state_file.is_none() && report.is_ok());
+    }
+}
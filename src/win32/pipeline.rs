@@ -0,0 +1,333 @@
+use std::{
+    path::{ Path, PathBuf },
+    time::{ Instant, SystemTime }
+};
+use windows::{
+    core::Error as WinError,
+    Win32::{
+        Foundation::HWND,
+        System::Com::CoTaskMemFree,
+        UI::Shell::{ IFileDialog, IFileDialogEvents, IShellItem, SIGDN_FILESYSPATH }
+    }
+};
+use crate::{ options::{ DialogFlags, options_diff }, utils::wide };
+use super::{
+    DialogError,
+    DialogKind,
+    FileDialog,
+    FileDialogManager,
+    FileDialogUtils,
+    OwnerResolution,
+    PathStyle,
+    PatternPolicy,
+    ResolvedConfig,
+    SelectionInfo,
+    StyledPath,
+    audit::{ AuditEntry, AuditOutcome, AuditStage },
+    confirm::{ OverwriteChoice, confirm_overwrite, unique_path },
+    events::DialogEvents
+};
+
+/// The sequence that every dialog goes through to be shown, shared between dialog types so that
+/// each step only has to be implemented once.
+pub(crate) trait DialogPipeline: FileDialog {
+    fn get_handle(&self) -> &IFileDialog;
+    fn get_manager_mut(&mut self) -> &mut FileDialogManager;
+    fn set_owner_resolution(&mut self, value: OwnerResolution);
+    fn set_selection_info(&mut self, value: Option<SelectionInfo>);
+    fn set_styled_results(&mut self, value: Vec<StyledPath>);
+    fn set_selected_filter(&mut self, value: Option<usize>);
+
+    /// Apply a resolved configuration, show the dialog and get the selected path. Successful
+    /// selections are remembered as the new default and in the folder history, unless
+    /// [`ResolvedConfig::remember_selection`] is turned off.
+    fn run(&mut self, config: &ResolvedConfig, events: DialogEvents) -> Result<Option<PathBuf>, DialogError> {
+        Ok(self.run_multiple(config, events)?.and_then(|paths| paths.into_iter().next()))
+    }
+
+    /// Like [`DialogPipeline::run`], but returns every selected path when the configuration
+    /// allows selecting more than one. The first path is the one that's remembered.
+    fn run_multiple(&mut self, config: &ResolvedConfig, events: DialogEvents) -> Result<Option<Vec<PathBuf>>, DialogError> {
+        self.run_recorded(config, events, true)
+    }
+
+    /// Show the dialog again after [`DialogPipeline::run`], without applying the configuration
+    /// a second time. Some settings such as the file types can only be set once per dialog, and
+    /// the dialog keeps the folder and filter it was left in.
+    fn run_again(&mut self, config: &ResolvedConfig, events: DialogEvents) -> Result<Option<PathBuf>, DialogError> {
+        Ok(self.run_recorded(config, events, false)?.and_then(|paths| paths.into_iter().next()))
+    }
+
+    fn run_recorded(&mut self, config: &ResolvedConfig, events: DialogEvents, configure: bool) -> Result<Option<Vec<PathBuf>>, DialogError> {
+        let started = SystemTime::now();
+        let timer = Instant::now();
+        let mut stage = AuditStage::Owner;
+        let out = self.run_stages(config, events, configure, &mut stage);
+        let log = self.get_manager_mut().get_audit_log_mut();
+        let verbose = log.get_verbose();
+        let outcome = match &out {
+            Ok(Some(_)) => AuditOutcome::Selected,
+            Ok(None) => AuditOutcome::Cancelled,
+            Err(e) => AuditOutcome::failed(stage, e, verbose)
+        };
+        let entry = AuditEntry::new(config, started, timer.elapsed(), outcome, verbose);
+        log.record(entry);
+        out
+    }
+
+    fn run_stages(&mut self, config: &ResolvedConfig, events: DialogEvents, configure: bool, stage: &mut AuditStage) -> Result<Option<Vec<PathBuf>>, DialogError> {
+        let owner = self.resolve_owner()?;
+        self.set_owner_resolution(owner);
+        self.set_selection_info(None);
+        self.set_styled_results(vec![]);
+        self.set_selected_filter(None);
+        if configure {
+            // so clear_persisted_state also covers GUIDs that were only given for one call
+            if let Some(guid) = config.client_guid {
+                self.get_manager_mut().register_client_guid(guid);
+            }
+            log::debug!("{}", config.describe());
+            *stage = AuditStage::Configure;
+            let before = get_options(self.get_handle())?;
+            config.apply(self.get_handle())?;
+            log::trace!("Dialog options: {}", options_diff(before, get_options(self.get_handle())?));
+        }
+        let mut events = events.owner(owner.window()).monitor(self.get_target_monitor());
+        if config.has_selection_checks() {
+            let checks = config.clone();
+            events = events.validator(Box::new(move |path| checks.check_selection(path).map_err(|e| e.to_string())));
+        }
+        let events: Option<IFileDialogEvents> = (!events.is_empty()).then(|| events.into());
+        *stage = AuditStage::Show;
+        let mut out = loop {
+            let shown = show(self.get_handle(), owner.window(), events.as_ref());
+            self.get_manager().get_completion_notification().fire(owner.window());
+            if !shown? {
+                return Ok(None);
+            }
+            let out = match config.is_multiselect() {
+                true => DialogEvents::get_selected_paths(self.get_handle())?,
+                false => vec![get_result(self.get_handle())?]
+            };
+            match (config.name_pattern.as_ref(), out.as_slice()) {
+                (Some((_, policy)), [path]) if ResolvedConfig::is_pattern(path) => match policy {
+                    PatternPolicy::Reprompt => {
+                        log::debug!("Pattern {} was accepted instead of a file, showing the dialog again", path.display());
+                        config.apply_name(self.get_handle())?;
+                    },
+                    PatternPolicy::Error => return Err(DialogError::PatternSelected(path.clone()))
+                },
+                _ => break out
+            }
+        };
+        let file_type = unsafe { self.get_handle().GetFileTypeIndex() }.unwrap_or(0);
+        self.set_selected_filter(config.selected_filter(file_type));
+        *stage = AuditStage::Check;
+        for path in &out {
+            config.check_selection(path)?;
+        }
+        if config.confirm_overwrite
+            && let [path] = out.as_mut_slice()
+            && path.exists() {
+            match confirm_overwrite(owner.window(), path)? {
+                OverwriteChoice::Overwrite => (),
+                OverwriteChoice::KeepBoth => *path = unique_path(path),
+                OverwriteChoice::Cancel => return Ok(None)
+            }
+        }
+        if config.verify_writable {
+            for path in &out {
+                FileDialogUtils::probe_writable(path).map_err(|source| DialogError::NotWritable { path: path.clone(), source })?;
+            }
+        }
+        if config.path_style != PathStyle::Native {
+            self.set_styled_results(out.iter().map(|p| StyledPath::new(p.clone(), config.path_style)).collect());
+        }
+        if let Some(first) = out.first() {
+            if config.collect_metadata {
+                self.set_selection_info(Some(SelectionInfo::query(first)));
+            }
+            if config.remember_selection {
+                self.remember(config.kind, first);
+            }
+        }
+        Ok(Some(out))
+    }
+
+    fn remember(&mut self, kind: DialogKind, path: &Path) {
+        let folder = match kind {
+            DialogKind::Drive => return,
+            DialogKind::OpenFolder => Some(path),
+            _ => path.parent()
+        };
+        self.set_default_path(path);
+        if let Some(folder) = folder {
+            self.get_manager_mut().get_history_mut().record(folder);
+        }
+        self.get_manager().autosave_state();
+    }
+}
+
+/// Show the dialog, attaching an event handler when something needs to be done to the dialog
+/// window after it opens. Returns false if the user cancelled, while any other failure from the
+/// shell is returned as an error.
+pub(crate) fn show(dialog: &IFileDialog, owner: Option<HWND>, events: Option<&IFileDialogEvents>) -> Result<bool, WinError> {
+    let cookie = match events {
+        Some(events) => Some(unsafe { dialog.Advise(events)? }),
+        None => None
+    };
+    let shown = keep_working_dir(|| unsafe { dialog.Show(owner) });
+    if let Some(cookie) = cookie {
+        unsafe { dialog.Unadvise(cookie)? };
+    }
+    match shown {
+        Ok(()) => Ok(true),
+        Err(e) if FileDialogUtils::is_cancelled(e.code()) => Ok(false),
+        Err(e) => Err(e)
+    }
+}
+
+/// The process's working directory before a dialog was shown. Even with NOCHANGEDIR, some
+/// navigations have been seen to move it.
+struct WorkingDir(Option<PathBuf>);
+
+impl WorkingDir {
+    fn snapshot() -> Self {
+        Self(std::env::current_dir().ok())
+    }
+
+    /// Change back to the snapshotted directory if it's changed, returning true if it had
+    fn restore(&self) -> bool {
+        let Some(before) = self.0.as_ref() else {
+            return false;
+        };
+        match std::env::current_dir() {
+            Ok(now) if now == *before => false,
+            now => {
+                log::warn!("Dialog changed the working directory to {:?}, restoring {}", now.ok(), before.display());
+                if let Err(e) = std::env::set_current_dir(before) {
+                    log::warn!("Couldn't restore the working directory: {}", e);
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Run a function, changing the working directory back afterwards if it moved
+pub(crate) fn keep_working_dir<F, T>(f: F) -> T where F: FnOnce() -> T {
+    let cwd = WorkingDir::snapshot();
+    let out = f();
+    cwd.restore();
+    out
+}
+
+/// Read the options currently set on the dialog
+pub(crate) fn get_options(dialog: &IFileDialog) -> Result<DialogFlags, WinError> {
+    Ok(DialogFlags::from_bits(unsafe { dialog.GetOptions()? }.0))
+}
+
+pub(crate) fn get_result(dialog: &IFileDialog) -> Result<PathBuf, DialogError> {
+    let res = unsafe { dialog.GetResult()? };
+    Ok(item_path(&res)?)
+}
+
+/// The file system path of a shell item. This goes straight from UTF-16 to an [`OsString`](std::ffi::OsString),
+/// so names with unpaired surrogates are returned as they are instead of failing.
+pub(crate) fn item_path(item: &IShellItem) -> Result<PathBuf, WinError> {
+    let path = unsafe { item.GetDisplayName(SIGDN_FILESYSPATH)? };
+    // the string has to be copied out before it's freed
+    let out = unsafe { wide::from_wide_until_nul(path.0) };
+    unsafe { CoTaskMemFree(Some(path.0 as _)) }
+    Ok(PathBuf::from(out))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ ffi::OsString, os::windows::ffi::OsStringExt, sync::Mutex };
+    use windows::{
+        core::HSTRING,
+        Win32::{
+            System::Com::{ CoInitializeEx, COINIT_APARTMENTTHREADED },
+            UI::Shell::SHCreateItemFromParsingName
+        }
+    };
+    use super::*;
+
+    // the working directory is shared by the whole process
+    static CWD: Mutex<()> = Mutex::new(());
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join("riri-file-dialog-cwd").join(name);
+        std::fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn working_dir_restored_after_success() {
+        let _lock = CWD.lock().unwrap_or_else(|e| e.into_inner());
+        let before = std::env::current_dir().unwrap();
+        let folder = temp_folder("success");
+        let out: Result<u32, WinError> = keep_working_dir(|| {
+            std::env::set_current_dir(&folder).unwrap();
+            Ok(1)
+        });
+        assert_eq!(out.unwrap(), 1);
+        assert_eq!(std::env::current_dir().unwrap(), before);
+    }
+
+    #[test]
+    fn working_dir_restored_after_error() {
+        let _lock = CWD.lock().unwrap_or_else(|e| e.into_inner());
+        let before = std::env::current_dir().unwrap();
+        let folder = temp_folder("error");
+        let out: Result<(), WinError> = keep_working_dir(|| {
+            std::env::set_current_dir(&folder).unwrap();
+            Err(WinError::empty())
+        });
+        assert!(out.is_err());
+        assert_eq!(std::env::current_dir().unwrap(), before);
+    }
+
+    #[test]
+    fn working_dir_unchanged() {
+        let _lock = CWD.lock().unwrap_or_else(|e| e.into_inner());
+        let cwd = WorkingDir::snapshot();
+        assert!(!cwd.restore());
+        let folder = temp_folder("unchanged");
+        let cwd = WorkingDir::snapshot();
+        let before = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&folder).unwrap();
+        assert!(cwd.restore());
+        assert_eq!(std::env::current_dir().unwrap(), before);
+    }
+
+    // Create a file with a name made of raw UTF-16 and read its path back through a shell item
+    fn item_path_of(name: &[u16]) -> (PathBuf, PathBuf) {
+        let _ = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+        let folder = std::env::temp_dir().join("riri-file-dialog-item-path");
+        std::fs::create_dir_all(&folder).unwrap();
+        let path = folder.join(OsString::from_wide(name));
+        std::fs::write(&path, b"").unwrap();
+        let item: IShellItem = unsafe { SHCreateItemFromParsingName(&HSTRING::from(path.as_os_str()), None) }.unwrap();
+        let out = item_path(&item).unwrap();
+        let _ = std::fs::remove_file(&path);
+        (path, out)
+    }
+
+    #[test]
+    fn item_path_keeps_unpaired_surrogate() {
+        // "a\u{D800}b.txt", which isn't valid UTF-16 but is a valid file name
+        let name = [0x61, 0xD800, 0x62, 0x2E, 0x74, 0x78, 0x74];
+        let (path, out) = item_path_of(&name);
+        assert_eq!(out, path);
+        assert!(out.to_str().is_none());
+    }
+
+    #[test]
+    fn item_path_keeps_non_ascii() {
+        let name: Vec<u16> = "ペルソナ.txt".encode_utf16().collect();
+        let (path, out) = item_path_of(&name);
+        assert_eq!(out, path);
+    }
+}
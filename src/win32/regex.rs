@@ -0,0 +1,467 @@
+use std::{
+    error::Error,
+    fmt::{ Display, Formatter }
+};
+
+// Repeat counts above this are refused, since names are never close to this long
+const MAX_REPEAT: usize = 1000;
+
+/// A regular expression that couldn't be compiled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    /// Offset in characters where the problem was found
+    pub position: usize,
+    pub message: &'static str
+}
+
+impl Display for RegexError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid regular expression at character {}: {}", self.position, self.message)
+    }
+}
+
+impl Error for RegexError {}
+
+/// A compiled regular expression for [`NamePattern::Regex`](super::NamePattern::Regex). This
+/// supports the common subset of the usual syntax: `.`, `[...]` sets with ranges and `^`
+/// negation, `\d`, `\w` and `\s` and their negations, groups, `|`, `*`, `+`, `?` and `{n,m}`.
+/// Like globs it has to match the whole name, and ignores ASCII case.
+#[derive(Debug, Clone)]
+pub struct Regex {
+    source: String,
+    root: Node
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Set { items: Vec<SetItem>, negate: bool },
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum SetItem {
+    Char(char),
+    Range(char, char),
+    // \d, \w or \s, and whether it's negated
+    Class(Shorthand, bool)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shorthand {
+    Digit,
+    Word,
+    Space
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, RegexError> {
+        let mut parser = Parser { chars: pattern.chars().collect(), pos: 0 };
+        let root = parser.alternation()?;
+        if parser.pos < parser.chars.len() {
+            // alternation only stops early at a closing bracket
+            return Err(parser.error("unmatched )"));
+        }
+        Ok(Self { source: pattern.to_owned(), root })
+    }
+
+    pub fn as_str(&self) -> &str { &self.source }
+
+    /// Whether the whole of `name` matches
+    pub fn is_match(&self, name: &str) -> bool {
+        let name: Vec<char> = name.chars().collect();
+        match_node(&self.root, &name, 0, &mut |p| p == name.len())
+    }
+}
+
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Eq for Regex {}
+
+impl Display for Regex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize
+}
+
+impl Parser {
+    fn error(&self, message: &'static str) -> RegexError {
+        RegexError { position: self.pos, message }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn alternation(&mut self) -> Result<Node, RegexError> {
+        let mut branches = vec![self.concat()?];
+        while self.eat('|') {
+            branches.push(self.concat()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.pop().unwrap(),
+            _ => Node::Alternate(branches)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, RegexError> {
+        let mut nodes = vec![];
+        while let Some(c) = self.peek() && c != '|' && c != ')' {
+            let atom = self.atom()?;
+            nodes.push(self.repeats(atom)?);
+        }
+        Ok(match nodes.len() {
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes)
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, RegexError> {
+        let start = self.pos;
+        let c = self.peek().unwrap();
+        self.pos += 1;
+        match c {
+            '(' => {
+                // groups don't capture anything, so (?:...) is the same
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let inner = self.alternation()?;
+                match self.eat(')') {
+                    true => Ok(inner),
+                    false => Err(RegexError { position: start, message: "unclosed group" })
+                }
+            },
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Start),
+            '$' => Ok(Node::End),
+            '[' => self.set(start),
+            '\\' => match self.escape()? {
+                SetItem::Class(class, negate) => Ok(Node::Set { items: vec![SetItem::Class(class, false)], negate }),
+                SetItem::Char(c) | SetItem::Range(c, _) => Ok(Node::Char(c))
+            },
+            '*' | '+' | '?' => Err(RegexError { position: start, message: "nothing to repeat" }),
+            c => Ok(Node::Char(c))
+        }
+    }
+
+    fn repeats(&mut self, mut node: Node) -> Result<Node, RegexError> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.counted()? {
+                    Some(v) => v,
+                    // not a count, so the { is read as a literal next
+                    None => return Ok(node)
+                },
+                _ => return Ok(node)
+            };
+            if self.pos == start {
+                self.pos += 1;
+            }
+            // lazy repeats match the same names when the whole name has to match
+            self.eat('?');
+            node = Node::Repeat { node: Box::new(node), min, max };
+        }
+    }
+
+    // Parse {n}, {n,} or {n,m}, leaving the position alone if it isn't one of those
+    fn counted(&mut self) -> Result<Option<(usize, Option<usize>)>, RegexError> {
+        let start = self.pos;
+        self.pos += 1;
+        let min = self.number();
+        let max = match self.eat(',') {
+            true => self.number(),
+            false => min
+        };
+        let (Some(min), true) = (min, self.eat('}')) else {
+            self.pos = start;
+            return Ok(None);
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(RegexError { position: start, message: "repeat range is reversed" });
+        }
+        if min.max(max.unwrap_or(0)) > MAX_REPEAT {
+            return Err(RegexError { position: start, message: "repeat count is too large" });
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        // saturate so that huge counts are reported as too large
+        (self.pos > start).then(|| self.chars[start..self.pos].iter()
+            .fold(0usize, |n, c| n.saturating_mul(10).saturating_add(c.to_digit(10).unwrap() as usize)))
+    }
+
+    // After a backslash, inside or outside a set
+    fn escape(&mut self) -> Result<SetItem, RegexError> {
+        let Some(c) = self.peek() else {
+            return Err(self.error("trailing backslash"));
+        };
+        let item = match c {
+            'd' | 'D' => SetItem::Class(Shorthand::Digit, c == 'D'),
+            'w' | 'W' => SetItem::Class(Shorthand::Word, c == 'W'),
+            's' | 'S' => SetItem::Class(Shorthand::Space, c == 'S'),
+            c if c.is_ascii_alphanumeric() => return Err(self.error("unsupported escape")),
+            c => SetItem::Char(c)
+        };
+        self.pos += 1;
+        Ok(item)
+    }
+
+    fn set(&mut self, start: usize) -> Result<Node, RegexError> {
+        let negate = self.eat('^');
+        let mut items = vec![];
+        loop {
+            let item_start = self.pos;
+            let Some(c) = self.peek() else {
+                return Err(RegexError { position: start, message: "unclosed character set" });
+            };
+            self.pos += 1;
+            let item = match c {
+                // ] first in the set is a literal
+                ']' if !items.is_empty() => return Ok(Node::Set { items, negate }),
+                '\\' => self.escape()?,
+                c => SetItem::Char(c)
+            };
+            let SetItem::Char(low) = item else {
+                items.push(item);
+                continue;
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|c| *c != ']') {
+                self.pos += 1;
+                let high = match self.peek().unwrap() {
+                    '\\' => {
+                        self.pos += 1;
+                        match self.escape()? {
+                            SetItem::Char(c) => c,
+                            _ => return Err(RegexError { position: item_start, message: "a class can't end a range" })
+                        }
+                    },
+                    c => {
+                        self.pos += 1;
+                        c
+                    }
+                };
+                if high < low {
+                    return Err(RegexError { position: item_start, message: "character range is reversed" });
+                }
+                items.push(SetItem::Range(low, high));
+            } else {
+                items.push(item);
+            }
+        }
+    }
+}
+
+impl SetItem {
+    fn matches(&self, c: char) -> bool {
+        match *self {
+            Self::Char(v) => v.eq_ignore_ascii_case(&c),
+            Self::Range(low, high) => [c, c.to_ascii_lowercase(), c.to_ascii_uppercase()].iter().any(|c| (low..=high).contains(c)),
+            Self::Class(class, negate) => negate != match class {
+                Shorthand::Digit => c.is_ascii_digit(),
+                Shorthand::Word => c.is_alphanumeric() || c == '_',
+                Shorthand::Space => c.is_whitespace()
+            }
+        }
+    }
+}
+
+// Backtracking match of `node` at `pos`, calling `next` with each position it could end at
+// until one of them lets the rest of the pattern match
+fn match_node(node: &Node, name: &[char], pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+    match node {
+        Node::Char(c) => name.get(pos).is_some_and(|n| n.eq_ignore_ascii_case(c)) && next(pos + 1),
+        Node::Any => pos < name.len() && next(pos + 1),
+        Node::Set { items, negate } => name.get(pos).is_some_and(|n| items.iter().any(|i| i.matches(*n)) != *negate) && next(pos + 1),
+        Node::Start => pos == 0 && next(pos),
+        Node::End => pos == name.len() && next(pos),
+        Node::Concat(nodes) => match_sequence(nodes, name, pos, next),
+        Node::Alternate(branches) => branches.iter().any(|b| match_node(b, name, pos, next)),
+        Node::Repeat { node, min, max } => match_repeat(node, *min, *max, 0, name, pos, next)
+    }
+}
+
+fn match_sequence(nodes: &[Node], name: &[char], pos: usize, next: &mut dyn FnMut(usize) -> bool) -> bool {
+    match nodes.split_first() {
+        None => next(pos),
+        Some((first, rest)) => match_node(first, name, pos, &mut |p| match_sequence(rest, name, p, next))
+    }
+}
+
+// Greedy: try one more repetition first, then stop here
+fn match_repeat(node: &Node, min: usize, max: Option<usize>, count: usize, name: &[char], pos: usize,
+    next: &mut dyn FnMut(usize) -> bool) -> bool {
+    if max != Some(count) {
+        let matched = match_node(node, name, pos, &mut |p| {
+            // an empty match can't make progress, so it's only repeated to reach the minimum
+            (p != pos || count < min) && match_repeat(node, min, max, count + 1, name, p, next)
+        });
+        if matched {
+            return true;
+        }
+    }
+    count >= min && next(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(name)
+    }
+
+    fn error(pattern: &str) -> RegexError {
+        Regex::new(pattern).unwrap_err()
+    }
+
+    #[test]
+    fn save_slot_example() {
+        let slot = r"SLOT_\d{2}\.sav";
+        assert!(matches(slot, "SLOT_01.sav"));
+        assert!(matches(slot, "slot_42.SAV"));
+        assert!(!matches(slot, "SLOT_1.sav"));
+        assert!(!matches(slot, "SLOT_001.sav"));
+        assert!(!matches(slot, "SLOT_01xsav"));
+        assert!(!matches(slot, "backup_SLOT_01.sav"));
+    }
+
+    #[test]
+    fn whole_name_has_to_match() {
+        assert!(!matches("abc", "abcd"));
+        assert!(!matches("bcd", "abcd"));
+        assert!(matches("^abc$", "abc"));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+
+    #[test]
+    fn repeats() {
+        assert!(matches("a*", ""));
+        assert!(matches("a*b", "aaab"));
+        assert!(matches("a+", "aaa"));
+        assert!(!matches("a+", ""));
+        assert!(matches("colou?r", "color"));
+        assert!(matches("colou?r", "colour"));
+        assert!(matches(r"\d{2,3}", "123"));
+        assert!(!matches(r"\d{2,3}", "1234"));
+        assert!(matches(r"\d{2,}", "12345"));
+        assert!(!matches(r"\d{2,}", "1"));
+        assert!(matches(".*?x", "abcx"));
+        // backtracking into a repeat
+        assert!(matches("a*ab", "aaab"));
+        assert!(matches("(a|ab)*c", "abac"));
+    }
+
+    #[test]
+    fn empty_repeats_terminate() {
+        assert!(matches("(a*)*", "aaa"));
+        assert!(matches("(a?){3}", ""));
+        assert!(!matches("(a*)*b", "aaaa"));
+    }
+
+    #[test]
+    fn brace_that_isnt_a_count_is_literal() {
+        assert!(matches("a{", "a{"));
+        assert!(matches("a{x}", "a{x}"));
+        assert!(matches("{1}", "{1}"));
+    }
+
+    #[test]
+    fn alternation_and_groups() {
+        assert!(matches("cat|dog", "dog"));
+        assert!(!matches("cat|dog", "catdog"));
+        assert!(matches(r"(cat|dog)_\d+\.png", "cat_12.png"));
+        assert!(matches(r"(?:cat|dog)s?", "dogs"));
+        assert!(matches("a(|b)c", "ac"));
+    }
+
+    #[test]
+    fn sets() {
+        assert!(matches("[abc]+", "cab"));
+        assert!(!matches("[abc]", "d"));
+        assert!(matches("[^abc]", "d"));
+        assert!(!matches("[^abc]", "a"));
+        assert!(matches("[a-f0-9]+", "dead42"));
+        assert!(matches("[]a]", "]"));
+        assert!(matches("[a-]", "-"));
+        assert!(matches(r"[\d_]+", "1_2"));
+        assert!(matches(r"[\]]", "]"));
+        assert!(matches(r"[!-\/]", "+"));
+    }
+
+    #[test]
+    fn shorthand_classes() {
+        assert!(matches(r"\w+", "save_01"));
+        assert!(!matches(r"\w+", "save-01"));
+        assert!(matches(r"\W", "-"));
+        assert!(matches(r"\s", " "));
+        assert!(matches(r"\S+", "abc"));
+        assert!(matches(r"\D+", "abc"));
+        assert!(!matches(r"\D", "1"));
+        assert!(matches(".", "é"));
+    }
+
+    #[test]
+    fn ascii_case_is_ignored() {
+        assert!(matches("ABC", "abc"));
+        assert!(matches("[A-C]+", "abc"));
+        assert!(matches("[a-c]+", "ABC"));
+        assert!(!matches("É", "é"));
+    }
+
+    #[test]
+    fn invalid_patterns() {
+        assert_eq!(error("(abc"), RegexError { position: 0, message: "unclosed group" });
+        assert_eq!(error("abc)"), RegexError { position: 3, message: "unmatched )" });
+        assert_eq!(error("[abc"), RegexError { position: 0, message: "unclosed character set" });
+        assert_eq!(error("*a"), RegexError { position: 0, message: "nothing to repeat" });
+        assert_eq!(error("a|+"), RegexError { position: 2, message: "nothing to repeat" });
+        assert_eq!(error(r"abc\"), RegexError { position: 4, message: "trailing backslash" });
+        assert_eq!(error(r"\b"), RegexError { position: 1, message: "unsupported escape" });
+        assert_eq!(error("a{3,1}"), RegexError { position: 1, message: "repeat range is reversed" });
+        assert_eq!(error("a{1001}"), RegexError { position: 1, message: "repeat count is too large" });
+        assert_eq!(error("a{99999999999999999999999}"), RegexError { position: 1, message: "repeat count is too large" });
+        assert_eq!(error("[z-a]"), RegexError { position: 1, message: "character range is reversed" });
+        assert_eq!(error(r"[a-\d]"), RegexError { position: 1, message: "a class can't end a range" });
+        assert_eq!(error("(").to_string(), "Invalid regular expression at character 0: unclosed group");
+    }
+
+    #[test]
+    fn equal_by_source() {
+        assert_eq!(Regex::new("a+").unwrap(), Regex::new("a+").unwrap());
+        assert_ne!(Regex::new("a+").unwrap(), Regex::new("aa*").unwrap());
+        assert_eq!(Regex::new(r"\d+").unwrap().to_string(), r"\d+");
+    }
+}
@@ -0,0 +1,172 @@
+use std::{
+    path::PathBuf,
+    sync::{ Arc, Mutex, MutexGuard, PoisonError, TryLockError }
+};
+use windows::Win32::Foundation::HWND;
+use super::{ FileDialogManager, ManagerError, OwnerSource };
+
+/// Identifies a top-level window's manager in the registry by the value of its handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WindowId(isize);
+
+impl WindowId {
+    pub fn from_hwnd(window: HWND) -> Self { Self(window.0 as isize) }
+    pub fn get_hwnd(&self) -> HWND { HWND(self.0 as _) }
+}
+
+impl From<HWND> for WindowId {
+    fn from(value: HWND) -> Self { Self::from_hwnd(value) }
+}
+
+/// A manager belonging to one window in the registry. Each has its own default folder, history
+/// and settings, and dialogs shown through it are owned by that window.
+#[derive(Debug, Clone)]
+pub struct WindowManager(Arc<Mutex<FileDialogManager>>);
+
+impl WindowManager {
+    fn lock(&self) -> MutexGuard<'_, FileDialogManager> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Run a function with this window's manager borrowed. Other windows' managers aren't locked.
+    pub fn with<F, R>(&self, f: F) -> R where F: FnOnce(&mut FileDialogManager) -> R {
+        f(&mut self.lock())
+    }
+
+    pub fn with_ref<F, R>(&self, f: F) -> R where F: FnOnce(&FileDialogManager) -> R {
+        f(&self.lock())
+    }
+
+    /// Whether this is still the manager registered for its window, which it stops being once
+    /// the window is removed with [`FileDialogManager::remove_window`]
+    pub fn is_registered(&self) -> bool {
+        lock_registry().iter().any(|(_, v)| Arc::ptr_eq(&v.0, &self.0))
+    }
+}
+
+static WINDOW_MANAGERS: Mutex<Vec<(WindowId, WindowManager)>> = Mutex::new(vec![]);
+
+fn lock_registry() -> MutexGuard<'static, Vec<(WindowId, WindowManager)>> {
+    WINDOW_MANAGERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl FileDialogManager {
+    /// Create a separate manager for a top-level window, for applications where more than one
+    /// window can open dialogs. Registering a window that already has a manager keeps the
+    /// existing one.
+    pub fn register_window(default: PathBuf, window: HWND) -> WindowId {
+        let id = WindowId::from_hwnd(window);
+        let mut registry = lock_registry();
+        if !registry.iter().any(|(k, _)| *k == id) {
+            let manager = Self::create(default, Some(OwnerSource::Window(window)));
+            registry.push((id, WindowManager(Arc::new(Mutex::new(manager)))));
+        }
+        id
+    }
+
+    /// Get the manager registered for a window
+    pub fn for_window(id: WindowId) -> Option<WindowManager> {
+        lock_registry().iter().find(|(k, _)| *k == id).map(|(_, v)| v.clone())
+    }
+
+    /// Run a function with a window's manager borrowed, like [`FileDialogManager::with`]
+    pub fn with_window<F, R>(id: WindowId, f: F) -> Result<R, ManagerError>
+    where F: FnOnce(&mut FileDialogManager) -> R {
+        Self::for_window(id).map(|m| m.with(f)).ok_or(ManagerError::Uninitialized)
+    }
+
+    /// Remove a window's manager from the registry, returning false if it wasn't registered. This
+    /// fails with [`ManagerError::InUse`] while a dialog is being shown through that manager.
+    ///
+    /// A [`WindowManager`] returned by [`FileDialogManager::for_window`] before this is called
+    /// keeps working, but it's no longer the registered one: registering the window again
+    /// creates a new manager that doesn't share its state. Check
+    /// [`WindowManager::is_registered`] before holding on to one for later.
+    pub fn remove_window(id: WindowId) -> Result<bool, ManagerError> {
+        let mut registry = lock_registry();
+        let Some(index) = registry.iter().position(|(k, _)| *k == id) else {
+            return Ok(false);
+        };
+        match registry[index].1.0.try_lock() {
+            Ok(_) | Err(TryLockError::Poisoned(_)) => (),
+            Err(TryLockError::WouldBlock) => return Err(ManagerError::InUse)
+        }
+        registry.remove(index);
+        Ok(true)
+    }
+
+    /// Every window that currently has a manager
+    pub fn get_registered_windows() -> Vec<WindowId> {
+        lock_registry().iter().map(|(k, _)| *k).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Window handles are only compared, so any value works as long as each test uses its own
+    fn window(value: isize) -> HWND {
+        HWND(value as _)
+    }
+
+    #[test]
+    fn register_and_look_up() {
+        let folder = std::env::temp_dir().join("riri-file-dialog-registry");
+        let id = FileDialogManager::register_window(folder.clone(), window(0x1001));
+        assert_eq!(id, WindowId::from_hwnd(window(0x1001)));
+        assert_eq!(id.get_hwnd(), window(0x1001));
+        assert!(FileDialogManager::get_registered_windows().contains(&id));
+        let manager = FileDialogManager::for_window(id).unwrap();
+        assert_eq!(manager.with_ref(|m| m.get_default_open().to_owned()), folder);
+        assert!(matches!(manager.with_ref(|m| m.get_owner().and_then(OwnerSource::resolve)), Some(w) if w == window(0x1001)));
+        assert!(FileDialogManager::for_window(WindowId::from_hwnd(window(0x1002))).is_none());
+        assert!(FileDialogManager::remove_window(id).unwrap());
+    }
+
+    #[test]
+    fn registering_again_keeps_existing() {
+        let id = FileDialogManager::register_window(PathBuf::from("first"), window(0x2001));
+        FileDialogManager::with_window(id, |m| m.set_default_save("changed")).unwrap();
+        assert_eq!(FileDialogManager::register_window(PathBuf::from("second"), window(0x2001)), id);
+        let (open, save) = FileDialogManager::with_window(id, |m| (m.get_default_open().to_owned(), m.get_default_save().to_owned())).unwrap();
+        assert_eq!(open, PathBuf::from("first"));
+        assert_eq!(save, PathBuf::from("changed"));
+        assert_eq!(FileDialogManager::get_registered_windows().iter().filter(|v| **v == id).count(), 1);
+        assert!(FileDialogManager::remove_window(id).unwrap());
+    }
+
+    #[test]
+    fn unknown_window() {
+        let id = WindowId::from_hwnd(window(0x3001));
+        assert_eq!(FileDialogManager::with_window(id, |_| ()), Err(ManagerError::Uninitialized));
+        assert_eq!(FileDialogManager::remove_window(id), Ok(false));
+    }
+
+    #[test]
+    fn remove_while_in_use() {
+        let id = FileDialogManager::register_window(PathBuf::new(), window(0x4001));
+        let manager = FileDialogManager::for_window(id).unwrap();
+        // stands in for a dialog being shown through the manager
+        let removed = manager.with(|_| FileDialogManager::remove_window(id));
+        assert_eq!(removed, Err(ManagerError::InUse));
+        assert!(manager.is_registered());
+        assert_eq!(FileDialogManager::remove_window(id), Ok(true));
+        assert_eq!(FileDialogManager::remove_window(id), Ok(false));
+    }
+
+    #[test]
+    fn clone_outlives_removal() {
+        let id = FileDialogManager::register_window(PathBuf::from("first"), window(0x5001));
+        let kept = FileDialogManager::for_window(id).unwrap();
+        assert!(FileDialogManager::remove_window(id).unwrap());
+        // the clone still works, but isn't the registered manager anymore
+        assert!(!kept.is_registered());
+        kept.with(|m| m.set_default_open("changed"));
+        FileDialogManager::register_window(PathBuf::from("second"), window(0x5001));
+        let registered = FileDialogManager::for_window(id).unwrap();
+        assert!(registered.is_registered());
+        assert_eq!(registered.with_ref(|m| m.get_default_open().to_owned()), PathBuf::from("second"));
+        assert!(FileDialogManager::remove_window(id).unwrap());
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::{ Path, PathBuf };
+
+/// How UNC prefixes like `\\server\share` are written by [`PathStyle::ForwardSlash`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UncStyle {
+    /// Leave the prefix as `\\server\share`
+    #[default]
+    Keep,
+    /// Write the prefix as `//server/share`
+    ForwardSlash
+}
+
+/// How selected paths are written in [`StyledPath::styled`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PathStyle {
+    /// Backslashes, as returned by the dialog
+    #[default]
+    Native,
+    /// Forward slashes, for config files shared with other platforms. Drive letters are kept.
+    ForwardSlash(UncStyle)
+}
+
+impl PathStyle {
+    /// Write a path in this style. Names that aren't valid Unicode are converted lossily, since
+    /// the result is meant for text files.
+    pub fn apply(&self, path: &Path) -> String {
+        let text = path.to_string_lossy();
+        let unc = match self {
+            Self::Native => return text.into_owned(),
+            Self::ForwardSlash(unc) => *unc
+        };
+        // Verbatim paths (\\?\C:\...) and device paths keep their whole form
+        if text.starts_with(r"\\?\") || text.starts_with(r"\\.\") {
+            return text.into_owned();
+        }
+        match text.strip_prefix(r"\\") {
+            Some(rest) if unc == UncStyle::Keep => {
+                // the prefix is \\server\share, everything after it is converted
+                let split = rest.match_indices('\\').nth(1).map_or(rest.len(), |(i, _)| i);
+                format!(r"\\{}{}", &rest[..split], rest[split..].replace('\\', "/"))
+            },
+            _ => text.replace('\\', "/")
+        }
+    }
+
+    /// Convert a path written by [`PathStyle::apply`] back to a native path
+    pub fn to_native(styled: &str) -> PathBuf {
+        PathBuf::from(styled.replace('/', "\\"))
+    }
+}
+
+/// A selected path along with its text in the dialog's [`PathStyle`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyledPath {
+    pub native: PathBuf,
+    pub styled: String
+}
+
+impl StyledPath {
+    pub fn new(native: PathBuf, style: PathStyle) -> Self {
+        let styled = style.apply(&native);
+        Self { native, styled }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORWARD: PathStyle = PathStyle::ForwardSlash(UncStyle::Keep);
+    const ALL_FORWARD: PathStyle = PathStyle::ForwardSlash(UncStyle::ForwardSlash);
+
+    fn apply(style: PathStyle, path: &str) -> String {
+        style.apply(Path::new(path))
+    }
+
+    #[test]
+    fn native_is_unchanged() {
+        assert_eq!(apply(PathStyle::Native, r"C:\saves\slot.sav"), r"C:\saves\slot.sav");
+        assert_eq!(apply(PathStyle::Native, r"\\server\share\slot.sav"), r"\\server\share\slot.sav");
+    }
+
+    #[test]
+    fn drive_paths() {
+        for style in [FORWARD, ALL_FORWARD] {
+            assert_eq!(apply(style, r"C:\saves\slot.sav"), "C:/saves/slot.sav");
+            assert_eq!(apply(style, r"C:\"), "C:/");
+            assert_eq!(apply(style, r"saves\slot.sav"), "saves/slot.sav");
+        }
+    }
+
+    #[test]
+    fn unc_prefix_kept() {
+        assert_eq!(apply(FORWARD, r"\\server\share\saves\slot.sav"), r"\\server\share/saves/slot.sav");
+        assert_eq!(apply(FORWARD, r"\\server\share"), r"\\server\share");
+        assert_eq!(apply(FORWARD, r"\\server"), r"\\server");
+    }
+
+    #[test]
+    fn unc_prefix_forward_slash() {
+        assert_eq!(apply(ALL_FORWARD, r"\\server\share\saves\slot.sav"), "//server/share/saves/slot.sav");
+        assert_eq!(apply(ALL_FORWARD, r"\\server\share"), "//server/share");
+    }
+
+    #[test]
+    fn verbatim_and_device_paths_are_unchanged() {
+        for style in [FORWARD, ALL_FORWARD] {
+            assert_eq!(apply(style, r"\\?\C:\saves\slot.sav"), r"\\?\C:\saves\slot.sav");
+            assert_eq!(apply(style, r"\\?\UNC\server\share\slot.sav"), r"\\?\UNC\server\share\slot.sav");
+            assert_eq!(apply(style, r"\\.\COM1"), r"\\.\COM1");
+        }
+    }
+
+    #[test]
+    fn round_trip_through_native() {
+        let paths = [
+            r"C:\saves\slot.sav",
+            r"saves\slot.sav",
+            r"\\server\share\saves\slot.sav",
+            r"\\?\C:\saves\slot.sav",
+            r"\\?\UNC\server\share\slot.sav"
+        ];
+        for style in [PathStyle::Native, FORWARD, ALL_FORWARD] {
+            for path in paths {
+                assert_eq!(PathStyle::to_native(&apply(style, path)), PathBuf::from(path), "{:?} {}", style, path);
+            }
+        }
+    }
+
+    #[test]
+    fn styled_path_keeps_both() {
+        let styled = StyledPath::new(PathBuf::from(r"C:\saves\slot.sav"), FORWARD);
+        assert_eq!(styled.native, PathBuf::from(r"C:\saves\slot.sav"));
+        assert_eq!(styled.styled, "C:/saves/slot.sav");
+    }
+}
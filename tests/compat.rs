@@ -0,0 +1,102 @@
+//! Checks that code written against the original `open`/`save` API still compiles and configures
+//! dialogs the same way now that both dialogs share one show pipeline. Dialogs are created and
+//! resolved but never shown, so these don't need a desktop session.
+#![cfg(windows)]
+
+use riri_file_dialog::dialog::{
+    DialogKind,
+    FileDialogManager,
+    FileTypeFilter,
+    InitialFolderSource,
+    ManagerConfig,
+    OpenDialog,
+    SaveDialog
+};
+use std::path::PathBuf;
+use windows::Win32::{
+    Foundation::HWND,
+    System::Com::{ CoInitializeEx, COINIT_APARTMENTTHREADED },
+    UI::Shell::{ FOS_NOCHANGEDIR, FOS_PICKFOLDERS }
+};
+
+fn setup(name: &str) -> (FileDialogManager, PathBuf) {
+    let _ = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+    let folder = std::env::temp_dir().join("riri-file-dialog-compat").join(name);
+    std::fs::create_dir_all(&folder).unwrap();
+    (ManagerConfig::new().default_path(folder.clone()).build(), folder)
+}
+
+fn camera_filter() -> [FileTypeFilter; 1] {
+    [FileTypeFilter::new("p5path".to_owned(), "P5R Freecam Path".to_owned())]
+}
+
+// The call sites from the README as they were written before the pipeline was shared. This is
+// only compiled, since showing the dialogs needs a user.
+#[allow(dead_code)]
+fn original_call_sites(default: PathBuf, window: HWND) {
+    FileDialogManager::new(default, window);
+    let mut dlg_lock = FileDialogManager::get();
+    if let Some(_path) = OpenDialog::new(dlg_lock.as_mut().unwrap()).unwrap().open(
+        Some(&camera_filter()),
+        Some("Open camera path")
+    ).unwrap() {}
+    if let Some(_path) = SaveDialog::new(dlg_lock.as_mut().unwrap()).unwrap().save(
+        Some(&camera_filter()),
+        Some("Save camera path")
+    ).unwrap() {}
+    if let Some(_path) = OpenDialog::new(dlg_lock.as_mut().unwrap()).unwrap().open_folder(None).unwrap() {}
+}
+
+#[test]
+fn open_resolves_like_before() {
+    let (mut manager, folder) = setup("open");
+    let dialog = OpenDialog::new(&mut manager).unwrap();
+    let config = dialog.resolve(Some(&camera_filter()), Some("Open camera path"));
+    assert_eq!(config.kind, DialogKind::Open);
+    assert_eq!(config.title, "Open camera path");
+    assert_eq!(config.initial_folder, folder);
+    assert_eq!(config.initial_folder_source, InitialFolderSource::ManagerDefault);
+    assert_eq!(config.filters.len(), 1);
+    assert_eq!(config.filters[0].description, "P5R Freecam Path");
+    assert_eq!(config.filters[0].spec, "*.p5path");
+    assert_eq!(config.suggested_name, None);
+    assert_eq!(config.options.0 & FOS_PICKFOLDERS.0, 0);
+}
+
+#[test]
+fn working_dir_kept_by_default() {
+    let (mut manager, _) = setup("working_dir");
+    let open = OpenDialog::new(&mut manager).unwrap().resolve(None, None);
+    assert_ne!(open.options.0 & FOS_NOCHANGEDIR.0, 0);
+    let save = SaveDialog::new(&mut manager).unwrap().resolve(None, None);
+    assert_ne!(save.options.0 & FOS_NOCHANGEDIR.0, 0);
+}
+
+#[test]
+fn open_default_title() {
+    let (mut manager, _) = setup("open_default_title");
+    let config = OpenDialog::new(&mut manager).unwrap().resolve(None, None);
+    assert_eq!(config.title, "Open a file");
+    assert!(config.filters.is_empty());
+}
+
+#[test]
+fn save_resolves_like_before() {
+    let (mut manager, folder) = setup("save");
+    let dialog = SaveDialog::new(&mut manager).unwrap();
+    let config = dialog.resolve(Some(&camera_filter()), None);
+    assert_eq!(config.kind, DialogKind::Save);
+    assert_eq!(config.title, "Save a file");
+    assert_eq!(config.initial_folder, folder);
+    assert_eq!(config.filters[0].spec, "*.p5path");
+    assert!(!config.confirm_overwrite);
+}
+
+#[test]
+fn open_folder_picks_folders() {
+    let (mut manager, folder) = setup("open_folder");
+    let config = OpenDialog::new(&mut manager).unwrap().resolve_folder(None);
+    assert_eq!(config.kind, DialogKind::OpenFolder);
+    assert_eq!(config.initial_folder, folder);
+    assert_ne!(config.options.0 & FOS_PICKFOLDERS.0, 0);
+}
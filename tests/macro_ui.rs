@@ -0,0 +1,87 @@
+//! Checks the compile errors that `define_dialog!` gives for bad input against the `.stderr`
+//! snapshots next to each fixture in tests/macro_ui. Every fixture is checked as its own crate
+//! that depends on this one, sharing a target folder so the dependencies are only built once.
+//! Run with `MACRO_UI=overwrite` to save the current errors as the new snapshots.
+#![cfg(windows)]
+
+use std::{
+    path::{ Path, PathBuf },
+    process::Command
+};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/macro_ui");
+
+fn fixtures() -> Vec<PathBuf> {
+    let mut out: Vec<PathBuf> = std::fs::read_dir(FIXTURES).unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "rs"))
+        .collect();
+    out.sort();
+    out
+}
+
+/// Write a crate for `fixture` and check it, returning the compiler's errors
+fn check(fixture: &Path) -> String {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let work = Path::new(env!("CARGO_TARGET_TMPDIR")).join("macro_ui");
+    let name = fixture.file_stem().unwrap().to_str().unwrap();
+    let project = work.join(name);
+    std::fs::create_dir_all(project.join("src")).unwrap();
+    std::fs::write(project.join("Cargo.toml"), format!(
+        "[package]\nname = \"macro-ui-{}\"\nversion = \"0.0.0\"\nedition = \"2024\"\npublish = false\n\n\
+        [dependencies]\nriri-file-dialog = {{ path = {:?} }}\n\n[workspace]\n",
+        name.replace('_', "-"), root)).unwrap();
+    std::fs::copy(fixture, project.join("src/main.rs")).unwrap();
+    // build with the same dependency versions as this crate
+    if root.join("Cargo.lock").exists() {
+        std::fs::copy(root.join("Cargo.lock"), project.join("Cargo.lock")).unwrap();
+    }
+    let output = Command::new(option_env!("CARGO").unwrap_or("cargo"))
+        .args(["check", "--quiet", "--color", "never"])
+        .current_dir(&project)
+        .env("CARGO_TARGET_DIR", work.join("target"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success(), "{} compiled, but it's meant to fail", fixture.display());
+    normalize(&String::from_utf8_lossy(&output.stderr), root)
+}
+
+/// Remove what changes between machines: the crate's location, path separators and the summary
+/// line from cargo
+fn normalize(stderr: &str, root: &Path) -> String {
+    let root = root.to_str().unwrap();
+    let mut out = String::new();
+    for line in stderr.lines() {
+        if line.starts_with("error: could not compile") {
+            continue;
+        }
+        let line = line.replace(root, "$CRATE");
+        let line = match line.trim_start().starts_with("-->") || line.trim_start().starts_with(":::") {
+            true => line.replace('\\', "/"),
+            false => line
+        };
+        out.push_str(line.trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+#[test]
+fn macro_errors_match_snapshots() {
+    let overwrite = std::env::var("MACRO_UI").is_ok_and(|v| v == "overwrite");
+    let mut mismatched = vec![];
+    for fixture in fixtures() {
+        let actual = check(&fixture);
+        let snapshot = fixture.with_extension("stderr");
+        if overwrite {
+            std::fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&snapshot).unwrap_or_default().replace("\r\n", "\n");
+        if actual != expected {
+            eprintln!("{} gave different errors:\n{}", fixture.display(), actual);
+            mismatched.push(fixture);
+        }
+    }
+    assert!(mismatched.is_empty(), "{} fixtures don't match their snapshots, run with MACRO_UI=overwrite if the new errors are right", mismatched.len());
+}
@@ -0,0 +1,3 @@
+riri_file_dialog::define_dialog!(import_model: open { title: "Import model", icon: "model.ico" });
+
+fn main() {}
@@ -0,0 +1,128 @@
+//! Drives a real file dialog from another thread by finding its window and sending it messages,
+//! the same way a user typing into it and clicking buttons would.
+
+use std::{
+    thread::{ self, JoinHandle },
+    time::{ Duration, Instant }
+};
+use windows::{
+    core::BOOL,
+    Win32::{
+        Foundation::{ HWND, LPARAM, WPARAM },
+        System::Threading::GetCurrentProcessId,
+        UI::WindowsAndMessaging::{
+            CB_SETCURSEL,
+            EnumChildWindows,
+            EnumWindows,
+            GetDlgCtrlID,
+            GetWindowTextW,
+            GetWindowThreadProcessId,
+            IDCANCEL,
+            IDOK,
+            PostMessageW,
+            SendMessageW,
+            WM_COMMAND,
+            WM_SETTEXT
+        }
+    }
+};
+
+// Control IDs in the common item dialog, see dlgs.h
+const FILE_NAME_COMBO: i32 = 0x047C; // cmb13
+const FILE_TYPE_COMBO: i32 = 0x0470; // cmb1
+const CBN_SELCHANGE: usize = 1;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A dialog window. HWNDs aren't Send, so this is stored as an integer to hand between threads.
+#[derive(Debug, Clone, Copy)]
+pub struct DialogWindow(isize);
+
+impl DialogWindow {
+    fn hwnd(&self) -> HWND { HWND(self.0 as _) }
+
+    /// Wait for a dialog with the given title to appear in this process
+    pub fn wait_for(title: &str) -> Self {
+        let start = Instant::now();
+        loop {
+            if let Some(window) = find_window(title) {
+                // give the dialog a moment to finish laying out its controls
+                thread::sleep(Duration::from_millis(250));
+                return Self(window.0 as isize);
+            }
+            assert!(start.elapsed() < TIMEOUT, "Dialog \"{}\" didn't appear", title);
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    pub fn set_file_name(&self, name: &str) -> &Self {
+        let control = find_control(self.hwnd(), FILE_NAME_COMBO).expect("Couldn't find file name box");
+        let text: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+        unsafe { SendMessageW(control, WM_SETTEXT, None, Some(LPARAM(text.as_ptr() as isize))) };
+        self
+    }
+
+    /// Select a file type by its zero-based index
+    pub fn select_filter(&self, index: usize) -> &Self {
+        let control = find_control(self.hwnd(), FILE_TYPE_COMBO).expect("Couldn't find file type box");
+        unsafe { SendMessageW(control, CB_SETCURSEL, Some(WPARAM(index)), None) };
+        // the dialog only reacts to the selection changing through its notification
+        let wparam = WPARAM((CBN_SELCHANGE << 16) | FILE_TYPE_COMBO as usize);
+        unsafe { SendMessageW(self.hwnd(), WM_COMMAND, Some(wparam), Some(LPARAM(control.0 as isize))) };
+        self
+    }
+
+    pub fn press_ok(&self) {
+        unsafe { PostMessageW(Some(self.hwnd()), WM_COMMAND, WPARAM(IDOK.0 as usize), LPARAM(0)).unwrap() };
+    }
+
+    pub fn press_cancel(&self) {
+        unsafe { PostMessageW(Some(self.hwnd()), WM_COMMAND, WPARAM(IDCANCEL.0 as usize), LPARAM(0)).unwrap() };
+    }
+}
+
+/// Run a set of actions against a dialog in the background while the test thread shows it
+pub fn drive<F>(title: &str, actions: F) -> JoinHandle<()>
+where F: FnOnce(DialogWindow) + Send + 'static {
+    let title = title.to_owned();
+    thread::spawn(move || actions(DialogWindow::wait_for(&title)))
+}
+
+struct Search<T> {
+    target: T,
+    found: Option<HWND>
+}
+
+fn find_window(title: &str) -> Option<HWND> {
+    let mut search = Search { target: title, found: None };
+    let _ = unsafe { EnumWindows(Some(find_window_proc), LPARAM(&raw mut search as isize)) };
+    search.found
+}
+
+unsafe extern "system" fn find_window_proc(window: HWND, param: LPARAM) -> BOOL {
+    let search = unsafe { &mut *(param.0 as *mut Search<&str>) };
+    let mut process = 0;
+    unsafe { GetWindowThreadProcessId(window, Some(&mut process)) };
+    let mut text = [0u16; 256];
+    let len = unsafe { GetWindowTextW(window, &mut text) } as usize;
+    if process == unsafe { GetCurrentProcessId() } && String::from_utf16_lossy(&text[..len]) == search.target {
+        search.found = Some(window);
+        return false.into();
+    }
+    true.into()
+}
+
+fn find_control(dialog: HWND, id: i32) -> Option<HWND> {
+    let mut search = Search { target: id, found: None };
+    let _ = unsafe { EnumChildWindows(Some(dialog), Some(find_control_proc), LPARAM(&raw mut search as isize)) };
+    search.found
+}
+
+unsafe extern "system" fn find_control_proc(window: HWND, param: LPARAM) -> BOOL {
+    let search = unsafe { &mut *(param.0 as *mut Search<i32>) };
+    if unsafe { GetDlgCtrlID(window) } == search.target {
+        search.found = Some(window);
+        return false.into();
+    }
+    true.into()
+}
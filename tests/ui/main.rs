@@ -0,0 +1,251 @@
+//! Interactive tests that show real dialogs and drive them through their window. These need a
+//! desktop session, so they only build with the `ui-tests` feature:
+//! ```text
+//! cargo test --features ui-tests --test ui -- --test-threads=1
+//! ```
+#![cfg(windows)]
+
+mod driver;
+
+use driver::{ DialogWindow, drive };
+use riri_file_dialog::{
+    dialog::{
+        DialogError,
+        DialogHandle,
+        DialogStatus,
+        FileDialogManager,
+        FileTypeFilter,
+        OpenDialog,
+        OwnerPolicy,
+        SaveDialog
+    },
+    options::DialogFlags
+};
+use std::{
+    path::PathBuf,
+    sync::{ mpsc, Mutex },
+    time::Duration
+};
+use windows::Win32::System::Com::{ CoInitializeEx, COINIT_APARTMENTTHREADED };
+
+// Scenarios share the global manager and each shows a modal dialog, so run one at a time
+static SCENARIO: Mutex<()> = Mutex::new(());
+
+/// Set up COM and a manager with no owner window, starting dialogs in a fresh temporary folder
+fn setup(name: &str) -> PathBuf {
+    let _ = unsafe { CoInitializeEx(None, COINIT_APARTMENTTHREADED) };
+    let folder = std::env::temp_dir().join("riri-file-dialog-ui").join(name);
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).unwrap();
+    FileDialogManager::new_deferred(folder.clone());
+    FileDialogManager::with(|mgr| mgr.set_owner_policy(OwnerPolicy::Unowned)).unwrap();
+    folder
+}
+
+fn filters() -> Vec<FileTypeFilter> {
+    vec![
+        FileTypeFilter::new("txt".to_owned(), "Text file".to_owned()),
+        FileTypeFilter::new("json".to_owned(), "JSON file".to_owned())
+    ]
+}
+
+#[test]
+fn open_typed_path() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("open_typed_path");
+    let target = folder.join("input.txt");
+    std::fs::write(&target, "").unwrap();
+    let typed = target.to_str().unwrap().to_owned();
+    let driver = drive("ui open typed path", move |dlg| { dlg.set_file_name(&typed).press_ok(); });
+    let result = FileDialogManager::with(|mgr| {
+        OpenDialog::new(mgr).unwrap().open(Some(&filters()), Some("ui open typed path"))
+    }).unwrap().unwrap();
+    driver.join().unwrap();
+    assert_eq!(result, Some(target));
+}
+
+#[test]
+fn open_cancel() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    setup("open_cancel");
+    let driver = drive("ui open cancel", |dlg| dlg.press_cancel());
+    let result = FileDialogManager::with(|mgr| {
+        OpenDialog::new(mgr).unwrap().open(None, Some("ui open cancel"))
+    }).unwrap().unwrap();
+    driver.join().unwrap();
+    assert_eq!(result, None);
+}
+
+#[test]
+fn open_unlocked_leaves_manager_available() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("open_unlocked_leaves_manager_available");
+    let target = folder.join("input.txt");
+    std::fs::write(&target, "").unwrap();
+    let typed = target.to_str().unwrap().to_owned();
+    let expected = folder.clone();
+    let driver = drive("ui open unlocked", move |dlg| {
+        // read the manager from another thread while the dialog is open
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || tx.send(FileDialogManager::with_ref(|mgr| mgr.get_default_open().to_owned())));
+        match rx.recv_timeout(Duration::from_secs(5)) {
+            Ok(read) => assert_eq!(read.unwrap(), expected),
+            Err(_) => {
+                dlg.press_cancel();
+                panic!("Manager was locked while the dialog was open");
+            }
+        }
+        dlg.set_file_name(&typed).press_ok();
+    });
+    let result = FileDialogManager::with_unlocked(|mgr| {
+        OpenDialog::new(mgr).unwrap().open(None, Some("ui open unlocked"))
+    }).unwrap().unwrap();
+    driver.join().unwrap();
+    assert_eq!(result, Some(target.clone()));
+    let default = FileDialogManager::with_ref(|mgr| mgr.get_default_open().to_owned()).unwrap();
+    assert_eq!(default, target);
+}
+
+#[test]
+fn open_async_allows_one_dialog() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    setup("open_async_allows_one_dialog");
+    let driver = drive("ui open async", |dlg| dlg.press_cancel());
+    let handle = DialogHandle::open(None, Some("ui open async")).unwrap();
+    assert!(matches!(OpenDialog::open_async(None, None, |_| ()), Err(DialogError::AsyncPending)));
+    driver.join().unwrap();
+    let result = loop {
+        match handle.try_result() {
+            DialogStatus::Open => std::thread::sleep(Duration::from_millis(50)),
+            DialogStatus::Closed(result) => break result,
+            DialogStatus::Stopped => panic!("the dialog thread stopped without a result")
+        }
+    };
+    assert_eq!(result.unwrap(), None);
+    assert!(matches!(handle.try_result(), DialogStatus::Stopped));
+}
+
+#[test]
+fn shutdown_cancels_async_dialog() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    setup("shutdown_cancels_async_dialog");
+    let handle = DialogHandle::open(None, Some("ui shutdown")).unwrap();
+    DialogWindow::wait_for("ui shutdown");
+    assert!(FileDialogManager::shutdown(Duration::from_secs(10)));
+    assert!(matches!(handle.try_result(), DialogStatus::Closed(Ok(None))));
+    assert!(FileDialogManager::try_get().is_none());
+}
+
+#[test]
+fn open_multiple_typed_names() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("open_multiple_typed_names");
+    for name in ["first.txt", "second.txt"] {
+        std::fs::write(folder.join(name), "").unwrap();
+    }
+    let driver = drive("ui open multiple", |dlg| { dlg.set_file_name("\"first.txt\" \"second.txt\"").press_ok(); });
+    let result = FileDialogManager::with(|mgr| {
+        OpenDialog::new(mgr).unwrap().open_multiple(Some(&filters()), Some("ui open multiple"))
+    }).unwrap().unwrap();
+    driver.join().unwrap();
+    let mut result = result.unwrap();
+    result.sort();
+    assert_eq!(result, vec![folder.join("first.txt"), folder.join("second.txt")]);
+}
+
+#[test]
+fn open_folder_sets_pick_folders() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    setup("open_folder_sets_pick_folders");
+    let driver = drive("ui open folder options", |dlg| dlg.press_cancel());
+    let options = FileDialogManager::with(|mgr| {
+        let mut dialog = OpenDialog::new(mgr).unwrap();
+        dialog.open_folder(Some("ui open folder options")).unwrap();
+        dialog.options().unwrap()
+    }).unwrap();
+    driver.join().unwrap();
+    assert!(options.contains(DialogFlags::PICK_FOLDERS), "{}", options);
+}
+
+#[test]
+fn save_name_in_default_folder() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("save_name_in_default_folder");
+    let driver = drive("ui save name", |dlg| { dlg.set_file_name("output.txt").press_ok(); });
+    let result = FileDialogManager::with(|mgr| {
+        SaveDialog::new(mgr).unwrap().save(Some(&filters()), Some("ui save name"))
+    }).unwrap().unwrap();
+    driver.join().unwrap();
+    assert_eq!(result, Some(folder.join("output.txt")));
+}
+
+#[test]
+fn save_with_second_filter() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("save_with_second_filter");
+    let driver = drive("ui save filter", |dlg| { dlg.select_filter(1).set_file_name("output.json").press_ok(); });
+    let result = FileDialogManager::with(|mgr| {
+        SaveDialog::new(mgr).unwrap().save(Some(&filters()), Some("ui save filter"))
+    }).unwrap().unwrap();
+    driver.join().unwrap();
+    assert_eq!(result, Some(folder.join("output.json")));
+}
+
+#[test]
+fn save_reports_selected_filter() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("save_reports_selected_filter");
+    let driver = drive("ui save selected filter", |dlg| { dlg.select_filter(1).set_file_name("output").press_ok(); });
+    let result = FileDialogManager::with(|mgr| {
+        SaveDialog::new(mgr).unwrap().save_with_selection(Some(&filters()), Some("ui save selected filter"))
+    }).unwrap().unwrap().unwrap();
+    driver.join().unwrap();
+    assert_eq!(result.filter_index, Some(1));
+    assert_eq!(result.path.parent(), Some(folder.as_path()));
+}
+
+#[test]
+fn save_skip_test_create_sets_flag() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    setup("save_skip_test_create_sets_flag");
+    let driver = drive("ui save skip test create", |dlg| dlg.press_cancel());
+    let options = FileDialogManager::with(|mgr| {
+        let mut dialog = SaveDialog::new(mgr).unwrap().skip_test_create();
+        dialog.save(None, Some("ui save skip test create")).unwrap();
+        dialog.options().unwrap()
+    }).unwrap();
+    driver.join().unwrap();
+    assert!(options.contains(DialogFlags::NO_TEST_FILE_CREATE), "{}", options);
+}
+
+#[test]
+fn open_keeps_working_directory() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    setup("open_keeps_working_directory");
+    let before = std::env::current_dir().unwrap();
+    let driver = drive("ui open working dir", |dlg| dlg.press_cancel());
+    let options = FileDialogManager::with(|mgr| {
+        let mut dialog = OpenDialog::new(mgr).unwrap();
+        dialog.open(None, Some("ui open working dir")).unwrap();
+        dialog.options().unwrap()
+    }).unwrap();
+    driver.join().unwrap();
+    assert!(options.contains(DialogFlags::NO_CHANGE_DIR), "{}", options);
+    assert_eq!(std::env::current_dir().unwrap(), before);
+}
+
+#[test]
+fn open_minimal_chrome_sets_flags() {
+    let _lock = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+    let folder = setup("open_minimal_chrome_sets_flags");
+    let driver = drive("ui open minimal chrome", |dlg| dlg.press_cancel());
+    let (options, places) = FileDialogManager::with(|mgr| {
+        let mut dialog = OpenDialog::new(mgr).unwrap().minimal_chrome().add_place(folder.clone());
+        let places = dialog.resolve(None, None).places;
+        dialog.open(None, Some("ui open minimal chrome")).unwrap();
+        (dialog.options().unwrap(), places)
+    }).unwrap();
+    driver.join().unwrap();
+    assert!(options.contains(DialogFlags::HIDE_MRU_PLACES | DialogFlags::HIDE_PINNED_PLACES), "{}", options);
+    assert_eq!(places, vec![folder]);
+}